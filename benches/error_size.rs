@@ -0,0 +1,46 @@
+//! Compares sending small messages through a channel whose error type is
+//! large with the same channel using `Box<E>` as the error type.
+//!
+//! Run with `cargo bench --bench error_size`.
+
+extern crate bchannel;
+
+use std::mem;
+use std::time::{Duration, Instant};
+
+use bchannel::{channel, CommMsg, Receiver, Sender};
+
+const MESSAGES: u64 = 1_000_000;
+const ROUNDS: usize = 5;
+
+/// An error type that is much larger than the messages.
+#[allow(dead_code)]
+struct LargeError([u64; 64]);
+
+/// Sends `MESSAGES` messages through a channel with error type `E`, then
+/// receives them, and returns how long it took.
+fn send_recv<E: Send + 'static>() -> Duration {
+    let (sx, rx): (Sender<u64, E>, Receiver<u64, E>) = channel();
+    let start = Instant::now();
+    for i in 0..MESSAGES {
+        sx.send(i).unwrap();
+    }
+    drop(sx);
+    let received = rx.iter().count();
+    let elapsed = start.elapsed();
+    assert_eq!(received as u64, MESSAGES);
+    elapsed
+}
+
+/// Returns the fastest of `ROUNDS` runs, in nanoseconds per message.
+fn best(run: fn() -> Duration) -> f64 {
+    let fastest = (0..ROUNDS).map(|_| run()).min().unwrap();
+    fastest.as_secs_f64() * 1e9 / MESSAGES as f64
+}
+
+fn main() {
+    println!("E = LargeError:      {:6.1} ns/message, {} bytes/message",
+             best(send_recv::<LargeError>), mem::size_of::<CommMsg<u64, LargeError>>());
+    println!("E = Box<LargeError>: {:6.1} ns/message, {} bytes/message",
+             best(send_recv::<Box<LargeError>>), mem::size_of::<CommMsg<u64, Box<LargeError>>>());
+}
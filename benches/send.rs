@@ -0,0 +1,76 @@
+//! Compares `Sender::send` for small `Copy` messages with sending the same
+//! messages through a bare `std::sync::mpsc` channel, and with sending them
+//! in batches with `Sender::send_batch`, which is the closest thing to a
+//! path specialised for `Copy` messages.
+//!
+//! Run with `cargo bench`.
+
+extern crate bchannel;
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use bchannel::{channel, CommMsg, Receiver, Sender};
+
+const MESSAGES: u64 = 1_000_000;
+const ROUNDS: usize = 5;
+const BATCH: usize = 1024;
+
+/// Sends `MESSAGES` messages through a bchannel and returns how long the
+/// sending took.
+fn bchannel_send() -> Duration {
+    let (sx, rx): (Sender<u64, ()>, Receiver<u64, ()>) = channel();
+    let start = Instant::now();
+    for i in 0..MESSAGES {
+        sx.send(i).unwrap();
+    }
+    let elapsed = start.elapsed();
+    drop(sx);
+    assert_eq!(rx.into_vec_block().unwrap().len() as u64, MESSAGES);
+    elapsed
+}
+
+/// Sends `MESSAGES` messages through a bchannel in batches of `BATCH` and
+/// returns how long the sending took, including filling the batches.
+fn bchannel_send_batch() -> Duration {
+    let (sx, rx): (Sender<u64, ()>, Receiver<u64, ()>) = channel();
+    let mut batch = Vec::with_capacity(BATCH);
+    let start = Instant::now();
+    for i in 0..MESSAGES {
+        batch.push(i);
+        if batch.len() == BATCH {
+            sx.send_batch(&mut batch).unwrap();
+        }
+    }
+    sx.send_batch(&mut batch).unwrap();
+    let elapsed = start.elapsed();
+    drop(sx);
+    assert_eq!(rx.into_vec_block().unwrap().len() as u64, MESSAGES);
+    elapsed
+}
+
+/// Sends `MESSAGES` messages through a bare mpsc channel and returns how
+/// long the sending took.
+fn mpsc_send() -> Duration {
+    let (sx, rx) = mpsc::channel::<CommMsg<u64, ()>>();
+    let start = Instant::now();
+    for i in 0..MESSAGES {
+        sx.send(CommMsg::Message(i)).unwrap();
+    }
+    let elapsed = start.elapsed();
+    drop(sx);
+    assert_eq!(rx.iter().count() as u64, MESSAGES);
+    elapsed
+}
+
+/// Returns the fastest of `ROUNDS` runs, in nanoseconds per message.
+fn best(run: fn() -> Duration) -> f64 {
+    let fastest = (0..ROUNDS).map(|_| run()).min().unwrap();
+    fastest.as_secs_f64() * 1e9 / MESSAGES as f64
+}
+
+fn main() {
+    println!("mpsc::Sender::send: {:6.1} ns/message", best(mpsc_send));
+    println!("Sender::send:       {:6.1} ns/message", best(bchannel_send));
+    println!("Sender::send_batch: {:6.1} ns/message", best(bchannel_send_batch));
+}
@@ -1,12 +1,250 @@
-use std::sync::{mpsc, RwLock};
-use std::cell::Cell;
+use std::sync::{mpsc, Arc, Condvar, Mutex, RwLock, Weak};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::convert::{Infallible, TryFrom};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::any::Any;
+use std::error;
+use std::fmt;
+use std::hint;
+use std::io;
+use std::mem;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "futures")]
+use std::task::{Context, Poll, Waker};
+
+#[cfg(feature = "futures")]
+extern crate futures_core;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 #[cfg(test)]
 mod test;
+#[cfg(feature = "futures")]
+mod stream;
+
+#[cfg(feature = "futures")]
+pub use stream::BchannelStream;
 
+/// A message as it travels through the inner `std::sync::mpsc` channel.
+///
+/// Every message in the queue takes up as much room as the largest of `T`
+/// and `E`, even though errors are rare, and is copied at that size on its
+/// way through.  If the error type is much larger than the messages, use
+/// `Box<E>` as the error type of the channel instead: `benches/error_size.rs`
+/// sends small messages with a 512 byte error type, which takes several
+/// times as long as with the error boxed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CommMsg<T, E> {
     Message(T),
     Error(E),
+    Close,
+    CloseWith(E),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Barrier(BarrierSignal),
+}
+
+/// The signal carried by `CommMsg::Barrier`, which completes the matching
+/// `BarrierToken` when it is fired or dropped.
+///
+/// The Receiver fires barriers as it passes them, so they never show up
+/// among the messages it returns.  Barrier signals never compare equal.
+#[derive(Clone)]
+pub struct BarrierSignal(mpsc::Sender<()>);
+
+/// A handle on a barrier sent with `Sender::barrier`.
+pub struct BarrierToken(mpsc::Receiver<()>);
+
+/// A flag shared between threads that interrupts
+/// `Receiver::recv_block_cancellable` once it is set.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+/// How often `Receiver::recv_block_cancellable` checks its token.
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+/// How long a wait on a channel that can't wake its waiters (see
+/// `Shared::polled`) lasts before the queue is checked again.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The result of a call to `Receiver::try_recv`.
+#[derive(Debug)]
+pub enum RecvStatus<T, E> {
+    /// A message was received.
+    Message(T),
+    /// There are no messages in the queue right now, but the channel is
+    /// still open.
+    Empty,
+    /// The channel has been closed.
+    Closed,
+    /// The channel was closed with an error.
+    Errored(E),
+}
+
+/// Why the channel stopped producing messages, returned by
+/// `Receiver::recv_all`.
+#[derive(Debug, PartialEq)]
+pub enum Terminal<E> {
+    /// The channel has been closed.
+    Closed,
+    /// The channel was closed with an error.
+    Errored(E),
+}
+
+/// The error returned by `Sender::send_err`, holding the message that
+/// failed to send.
+#[derive(Debug, PartialEq)]
+pub struct SendError<T>(pub T);
+
+/// The error returned by `Sender::try_send`, holding the message that
+/// failed to send.
+#[derive(Debug, PartialEq)]
+pub enum SendErr<T> {
+    /// The receiving end of the channel is gone.
+    Disconnected(T),
+    /// An earlier send on this Sender already failed, so the message
+    /// wasn't sent.
+    Closed(T),
+    /// The channel is bounded and full.  The Sender stays usable.
+    Full(T),
+}
+
+/// The error returned by `Sender::send2`, holding the message that failed
+/// to send.
+#[derive(Debug, PartialEq)]
+pub enum TrySendError<T> {
+    /// The channel is bounded and full.  This never happens on an
+    /// unbounded channel.
+    Full(T),
+    /// The receiving end of the channel is gone.
+    Disconnected(T),
+}
+
+/// What a Sender did, reported to the observer set with
+/// `Sender::with_observer`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SendEvent {
+    /// A message was sent.
+    Sent,
+    /// A message or an error failed to send because the receiving end of
+    /// the channel is gone.
+    Failed,
+    /// An error was sent.
+    Errored,
+    /// The channel was closed with `close` or `close_with`.
+    Closed,
+}
+
+/// A callback that is told about everything a Sender does.
+type Observer = Arc<dyn Fn(SendEvent) + Send + Sync>;
+
+/// The error returned by `Sender::reserve` when the channel is closed.
+#[derive(Debug, PartialEq)]
+pub struct ReserveError;
+
+/// A place in the queue of a bounded channel, reserved by `Sender::reserve`.
+///
+/// The place is given back if the permit is dropped without sending.
+pub struct Permit<'a, T: Send + 'a, E: Send + 'a> {
+    sender: &'a Sender<T, E>,
+    used: bool
+}
+
+/// The error returned by `Sender::send_timeout`.
+#[derive(Debug, PartialEq)]
+pub enum SendTimeoutError<T> {
+    /// The channel stayed full for the whole timeout.
+    Timeout(T),
+    /// The receiving end of the channel is gone.
+    Disconnected(T),
+}
+
+/// The error returned by `Receiver::recv_timeout_raw`.
+#[derive(Debug, PartialEq)]
+pub enum RecvTimeoutError<E> {
+    /// No message arrived before the timeout.  The channel is still open.
+    Timeout,
+    /// The channel is closed.
+    Disconnected,
+    /// The channel was closed with an error.
+    Errored(E),
+}
+
+/// The error returned by `Sender::try_send_all`, holding the message that
+/// failed to send and the rest of the iterator.
+#[derive(Debug)]
+pub enum SendAllStop<T, I> {
+    /// The channel is full.  Sending can be resumed later with the
+    /// message and the iterator.
+    Full(T, I),
+    /// The receiving end of the channel is gone.
+    Disconnected(T, I),
+}
+
+/// The error returned by `Sender::send_result`, holding whichever payload
+/// failed to send.
+#[derive(Debug, PartialEq)]
+pub enum SendResultError<T, E> {
+    /// A message failed to send.
+    Message(T),
+    /// An error failed to send.
+    Error(E),
+}
+
+/// How a channel was closed, returned by `Receiver::close_reason`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloseReason {
+    /// The channel was closed without an error.
+    Clean,
+    /// The channel was closed by an error.
+    Error,
+}
+
+/// A snapshot of the state of a Receiver, returned by `Receiver::status`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReceiverStatus {
+    /// Whether the channel is closed, as returned by `is_closed`.
+    pub closed: bool,
+    /// Whether the channel was closed with an error, as returned by
+    /// `has_error`.
+    pub errored: bool,
+    /// The number of queued messages, as returned by `len`.
+    pub len: usize,
+}
+
+/// Why a `TimeoutIterator` stopped, returned by
+/// `TimeoutIterator::stopped_reason`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// The channel was closed, regularly or by an error.
+    Closed,
+    /// No message arrived within the idle timeout.
+    Idle,
+}
+
+/// One of two values, returned by `select2`.
+#[derive(Debug, PartialEq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// The error of a receiver returned by `zip`: `Left` for an error from
+/// the first input, `Right` for one from the second.
+pub type ZipError<E1, E2> = Either<E1, E2>;
+
+enum SenderKind<T, E> {
+    Unbounded(mpsc::Sender<CommMsg<T, E>>),
+    Bounded(mpsc::SyncSender<CommMsg<T, E>>),
+    /// Discards everything, see `Sender::null`.
+    Null
 }
 
 #[allow(dead_code)]
@@ -15,18 +253,175 @@ enum MaybeOwned<'a, A: 'a> {
     Borrowed(&'a A)
 }
 
+/// State shared between the ends of a channel.
+struct Shared {
+    /// The number of messages (including errors) sent but not received.
+    len: AtomicUsize,
+    /// The number of live Senders.
+    senders: AtomicUsize,
+    /// Set once the Receiver is dropped.
+    receiver_dropped: AtomicBool,
+    /// Set by `Receiver::request_close`.
+    close_requested: AtomicBool,
+    /// The free places of a bounded channel, see `Sender::reserve`.
+    slots: Option<Slots>,
+    /// The number of threads waiting on `notify` or on one of `watchers`.
+    waiters: AtomicUsize,
+    /// Wakes the threads blocked receiving from this channel.
+    notify: Notify,
+    /// Wakes the threads waiting on this channel and others at once, see
+    /// `Watch`.
+    watchers: Mutex<Vec<Arc<Notify>>>,
+    /// Set once messages can reach the queue without going through a
+    /// Sender of this channel, e.g. after `Sender::into_inner`.
+    untracked: AtomicBool,
+    /// Set for a channel with a bound of 0, whose Senders only finish
+    /// sending, and so only wake the waiters, once the message is taken.
+    rendezvous: bool,
+    /// The task waiting in `Receiver::poll_recv`.
+    #[cfg(feature = "futures")]
+    waker: Mutex<Option<Waker>>
+}
+
+/// Counts the free places in the queue of a bounded channel.
+///
+/// Every message and error takes a place before it is sent and gives it
+/// back once it is received, so a place that is held by a `Permit` can't
+/// be taken by another send.
+struct Slots {
+    /// The number of free places, or None once the Receiver is gone.
+    free: Mutex<Option<usize>>,
+    capacity: usize,
+    freed: Condvar
+}
+
+/// Lets threads wait until something changes on one or more channels.
+struct Notify {
+    /// Moves on every time `notify` is called.
+    epoch: Mutex<u64>,
+    changed: Condvar
+}
+
+/// Counts a thread as waiting on `Shared::notify` for as long as it
+/// exists.
+struct Waiting<'a>(&'a Shared);
+
+/// Wakes one thread when something changes on any of several channels.
+///
+/// This is what `select2` and `zip` wait on, since a thread can only
+/// wait for one `std::sync::mpsc` channel at a time.
+struct Watch<'a> {
+    notify: Arc<Notify>,
+    channels: Vec<&'a Shared>
+}
+
+/// A Sender's handle on the shared state, which counts as a live sender
+/// for as long as it exists.
+struct SenderShared(Arc<Shared>);
+
+/// The Receiver's handle on the shared state, which marks the receiver as
+/// dropped when it goes away, unless it was detached by `into_parts`.
+struct ReceiverShared {
+    shared: Arc<Shared>,
+    detached: bool
+}
+
 /// The sending end of the channel.
 pub struct Sender<T : Send, E : Send> {
     closed: Cell<bool>,
-    inner: mpsc::Sender<CommMsg<T, E>>
+    label: Option<&'static str>,
+    observer: Option<Observer>,
+    /// Sent when the Sender is dropped, see `arm_drop_error`.
+    drop_error: Option<E>,
+    // Dropped before `shared`, so that the channel has disconnected by the
+    // time the last Sender wakes the Receiver.
+    inner: Arc<SenderKind<T, E>>,
+    shared: SenderShared
+}
+
+/// A handle on a sending end of the channel that doesn't keep the channel
+/// open.
+///
+/// This is created by `Sender::downgrade`.  The channel closes once every
+/// Sender is gone, even if weak handles remain, and after that `upgrade`
+/// returns None.
+pub struct WeakSender<T : Send, E : Send> {
+    label: Option<&'static str>,
+    observer: Option<Observer>,
+    shared: Arc<Shared>,
+    inner: Weak<SenderKind<T, E>>
+}
+
+/// A sending end of the channel that transforms messages of type U into
+/// messages of type T before sending them.
+///
+/// This is created by `Sender::map`.
+pub struct MappedSender<U, T : Send, E : Send, F> {
+    sender: Sender<T, E>,
+    f: F,
+    _marker: PhantomData<fn(U)>
+}
+
+/// A sending end that sends every message through two channels.
+///
+/// This is created by `Sender::tee`.
+pub struct TeeSender<T : Send, E : Send> {
+    first: Sender<T, E>,
+    second: Sender<T, E>
+}
+
+/// Sends every message to all of its subscribers.
+///
+/// Each subscriber gets its own channel, so a slow subscriber doesn't hold
+/// up the others.
+pub struct Broadcaster<T : Send, E : Send> {
+    senders: Mutex<Vec<Sender<T, E>>>
 }
 
 /// The receiving end of the channel.
+///
+/// A Receiver can be shared between threads by reference if the error type
+/// is `Sync`, since the errors it holds can be looked at from any of them.
+/// Receives from several threads take turns on the queue, and each message
+/// goes to one of them.
+///
+/// Once the Receiver is dropped, including while unwinding from a panic,
+/// every Sender reports `is_closed` and sends fail instead of blocking.
 pub struct Receiver<T : Send, E : Send> {
-    closed: Cell<bool>,
-    errored: Cell<bool>,
-    error: RwLock<Option<E>>,
-    inner: mpsc::Receiver<CommMsg<T, E>>
+    closed: AtomicBool,
+    label: Option<&'static str>,
+    errored: AtomicBool,
+    closed_by_error: AtomicBool,
+    ended: AtomicBool,
+    collect_errors: AtomicBool,
+    drain_on_error: AtomicBool,
+    errors: RwLock<Vec<E>>,
+    shared: ReceiverShared,
+    pending_error: Mutex<Option<E>>,
+    /// The `Arc<E>` handed out by `error_arc`.  It is type-erased so that
+    /// the Receiver stays `Send` for error types that aren't `Sync`.
+    shared_error: Mutex<Option<Box<dyn Any + Send>>>,
+    close_value: Mutex<Option<E>>,
+    front: Mutex<VecDeque<T>>,
+    inner: Mutex<mpsc::Receiver<CommMsg<T, E>>>
+}
+
+/// A receiving end of the channel that converts the error of type E into
+/// an error of type E2.
+///
+/// This is created by `Receiver::map_err`.
+pub struct MapErrReceiver<T : Send, E : Send, E2, F> {
+    receiver: Receiver<T, E>,
+    f: Cell<Option<F>>,
+    error: RefCell<Option<E2>>
+}
+
+/// A receiving end of the channel that can be cloned and shared between
+/// threads.  Each message is received by exactly one of the clones.
+///
+/// This is created by `Receiver::into_shared`.
+pub struct SharedReceiver<T : Send, E : Send> {
+    inner: Arc<Mutex<Receiver<T, E>>>
 }
 
 /// An iterator over received items.
@@ -36,247 +431,3097 @@ pub struct Receiver<T : Send, E : Send> {
 ///
 /// This struct can either block when waiting for a message, or it can finish
 /// early (and be reusable) when it runs out of messages in the queue.
+/// Once it has seen the channel closed, it keeps returning `None`.
 pub struct ReceiverIterator<'a, T: Send + 'a, E: Send + 'a> {
+    reference: MaybeOwned<'a, Receiver<T, E>>,
+    blocking: bool,
+    /// Set once the channel was seen closed, so later calls skip the
+    /// receiver entirely.
+    done: bool
+}
+
+/// An iterator over received items that also yields the error that
+/// closed the channel.
+///
+/// Messages are yielded as `Ok`, and if the channel was closed with an
+/// error, the error is yielded as a final `Err` before the iterator ends.
+/// Like `ReceiverIterator`, this can either block when waiting for a
+/// message or finish early when it runs out of messages in the queue.
+pub struct ResultIterator<'a, T: Send + 'a, E: Send + 'a> {
     reference: MaybeOwned<'a, Receiver<T, E>>,
     blocking: bool
 }
 
-impl <'a, A> MaybeOwned<'a, A> {
-    fn borrow<'b: 'a>(&'b self) -> &'b A  {
-        match *self {
-            MaybeOwned::Owned(ref a) => a,
-            MaybeOwned::Borrowed(a) => a
-        }
-    }
+/// An iterator over received items that counts them and records how the
+/// iteration ended.
+///
+/// This is created by `ReceiverIterator::counted`.
+pub struct CountedIterator<'a, T: Send + 'a, E: Send + 'a> {
+    iter: ReceiverIterator<'a, T, E>,
+    yielded: usize,
+    terminated_by: Option<CloseReason>
 }
 
-/// Returns a Sender-Receiver pair sending messages of type T, and
-/// can fail with an error of type E.
-pub fn channel<T, E>() -> (Sender<T, E>, Receiver<T, E>)
-where T: Send + 'static, E: Send + 'static{
-    let (tx, rx) = mpsc::channel();
-    (Sender::from_old(tx), Receiver::from_old(rx))
+/// A blocking iterator over received items that gives up when no message
+/// arrives within an idle timeout.
+///
+/// This is created by `Receiver::timeout_iter`.  Like the non-blocking
+/// `ReceiverIterator`, it can be reused after it runs out of messages.
+pub struct TimeoutIterator<'a, T: Send + 'a, E: Send + 'a> {
+    receiver: &'a Receiver<T, E>,
+    idle: Duration,
+    stopped: Option<StopReason>
 }
 
-impl <T, E> Sender<T, E>
-where T: Send + 'static, E: Send + 'static {
-    /// Converts an old-stype Sender to a bchannel Sender.
-    pub fn from_old(v: mpsc::Sender<CommMsg<T, E>>) -> Sender<T, E> {
-        Sender {
-            closed: Cell::new(false),
-            inner: v
+/// A blocking iterator over received items that stops at the first message
+/// matching a predicate.
+///
+/// This is created by `Receiver::iter_until`.
+pub struct UntilIterator<'a, T: Send + 'a, E: Send + 'a, F> {
+    receiver: &'a Receiver<T, E>,
+    stop: F,
+    done: bool
+}
+
+/// A blocking iterator over received items that stops at the first message
+/// equal to a poison value.
+///
+/// This is created by `Receiver::iter_until_value`.
+pub struct UntilValueIterator<'a, T: Send + 'a, E: Send + 'a> {
+    receiver: &'a Receiver<T, E>,
+    poison: T,
+    done: bool
+}
+
+/// An iterator over received items that skips messages equal to the one
+/// it yielded last.
+///
+/// This is created by `Receiver::iter_dedup`.  Like the non-blocking
+/// `ReceiverIterator`, it can be reused after it runs out of messages, and
+/// it remembers the last message across uses.
+pub struct DedupIterator<'a, T: Send + 'a, E: Send + 'a> {
+    inner: ReceiverIterator<'a, T, E>,
+    last: Option<T>
+}
+
+/// A reader over the bytes of the chunks received by a Receiver.
+///
+/// This is created by `Receiver::into_read`.
+pub struct ChannelRead<T: Send, E: Send> {
+    receiver: Receiver<T, E>,
+    /// The chunk that is being read, and how much of it has been read.
+    chunk: Option<(T, usize)>
+}
+
+/// A received message that is put back into the Receiver unless it is
+/// acknowledged.
+///
+/// This is created by `Receiver::recv_guarded`, and derefs to the message.
+pub struct RecvGuard<'a, T: Send + 'a, E: Send + 'a> {
+    receiver: &'a Receiver<T, E>,
+    msg: Option<T>
+}
+
+impl <T, E> SenderKind<T, E> {
+    fn send(&self, m: CommMsg<T, E>) -> Result<(), mpsc::SendError<CommMsg<T, E>>> {
+        match *self {
+            SenderKind::Unbounded(ref s) => s.send(m),
+            SenderKind::Bounded(ref s) => s.send(m),
+            SenderKind::Null => Ok(())
         }
     }
 
-    /// Returns the old-style Sender that is containd inside this Sender.
-    pub fn into_inner(self) -> mpsc::Sender<CommMsg<T, E>> {
-        self.inner
+    /// Like `send`, but fails instead of blocking when a bounded channel
+    /// is full.  An unbounded channel is never full.
+    fn try_send(&self, m: CommMsg<T, E>) -> Result<(), mpsc::TrySendError<CommMsg<T, E>>> {
+        match *self {
+            SenderKind::Unbounded(ref s) => s.send(m).map_err(|mpsc::SendError(m)| {
+                mpsc::TrySendError::Disconnected(m)
+            }),
+            SenderKind::Bounded(ref s) => s.try_send(m),
+            SenderKind::Null => Ok(())
+        }
     }
+}
 
-    /// Sends a message through the channel.  Returns `Ok(())` if the sending
-    /// might succeed, and returns an Err with the message that you tried to
-    /// send in the event that the sending surely failed.
-    pub fn send(&self, t: T) -> Result<(), T> {
-        match self.inner.send(CommMsg::Message(t)) {
-            Ok(()) => Ok(()),
-            Err(mpsc::SendError(CommMsg::Message(a))) => {
-                self.closed.set(true);
-                Err(a)
-            },
-            Err(_) => unreachable!()
-        }
+impl Shared {
+    fn new() -> Arc<Shared> {
+        Arc::new(Shared {
+            len: AtomicUsize::new(0),
+            senders: AtomicUsize::new(0),
+            receiver_dropped: AtomicBool::new(false),
+            close_requested: AtomicBool::new(false),
+            slots: None,
+            waiters: AtomicUsize::new(0),
+            notify: Notify::new(),
+            watchers: Mutex::new(Vec::new()),
+            untracked: AtomicBool::new(false),
+            rendezvous: false,
+            #[cfg(feature = "futures")]
+            waker: Mutex::new(None)
+        })
     }
 
-    /// Tries to send all of the messages in an iterator.  Returns Ok(()) if the
-    /// sending might succeed and returns Err with a tuple containing the message
-    /// that failed to send, and the remaining iterator.
-    pub fn send_all<I: Iterator<Item=T>>(&self, mut i: I) -> Result<(), (T, I)> {
-        loop {
-            match i.next() {
-                None => break,
-                Some(x) => {
-                    match self.send(x) {
-                        Ok(()) => {}
-                        Err(x) => return Err((x, i))
-                    }
-                }
-            }
-        }
-        Ok(())
+    /// Returns the state for a channel with a bound of 0.
+    fn rendezvous() -> Arc<Shared> {
+        let mut shared = Shared::new();
+        Arc::get_mut(&mut shared).unwrap().rendezvous = true;
+        shared
     }
 
-    /// Closes the sending end of the channel.
-    pub fn close(self) { }
+    /// Returns the state for a channel of at most `bound` messages.
+    fn bounded(bound: usize) -> Arc<Shared> {
+        let mut shared = Shared::new();
+        Arc::get_mut(&mut shared).unwrap().slots = Some(Slots {
+            free: Mutex::new(Some(bound)),
+            capacity: bound,
+            freed: Condvar::new()
+        });
+        shared
+    }
 
-    /// Closes the sending end of the channel with an error.
-    pub fn error(self, e: E) -> Result<(), E> {
-        match self.inner.send(CommMsg::Error(e)) {
-            Ok(()) => Ok(()),
-            Err(mpsc::SendError(CommMsg::Error(a))) => {
-                self.closed.set(true);
-                Err(a)
+    /// Takes a free place in a bounded channel, blocking until there is
+    /// one.  Once the Receiver is gone this returns straight away.
+    fn acquire_slot(&self) {
+        if let Some(ref slots) = self.slots {
+            let mut free = slots.free.lock().unwrap();
+            while *free == Some(0) {
+                free = slots.freed.wait(free).unwrap();
+            }
+            if let Some(ref mut n) = *free {
+                *n -= 1;
             }
-            Err(_) => unreachable!()
         }
     }
 
-    /// Returns true if any message has failed to send.
-    pub fn is_closed(&self) -> bool {
-        self.closed.get()
+    /// Like `acquire_slot`, but returns false instead of blocking.
+    fn try_acquire_slot(&self) -> bool {
+        match self.slots {
+            Some(ref slots) => match *slots.free.lock().unwrap() {
+                Some(0) => false,
+                Some(ref mut n) => {
+                    *n -= 1;
+                    true
+                }
+                None => true
+            },
+            None => true
+        }
     }
-}
 
-impl <T, E> Clone for Sender<T, E>
-where T: Send + 'static, E: Send + 'static {
-    fn clone(&self) -> Sender<T, E> {
-        Sender {
-            inner: self.inner.clone(),
-            closed: Cell::new(self.closed.get())
+    /// Gives back a place taken by `acquire_slot`.
+    fn release_slot(&self) {
+        if let Some(ref slots) = self.slots {
+            if let Some(ref mut n) = *slots.free.lock().unwrap() {
+                *n = (*n + 1).min(slots.capacity);
+            }
+            slots.freed.notify_one();
         }
     }
-}
-
 
-impl <T, E> Receiver<T, E>
-where T: Send + 'static, E: Send + 'static {
-    /// Converts an old-style receiver to a bchannel receiver.
-    pub fn from_old(v: mpsc::Receiver<CommMsg<T, E>>) -> Receiver<T, E> {
-        Receiver {
-            closed: Cell::new(false),
-            errored: Cell::new(false),
-            error: RwLock::new(None),
-            inner: v
+    /// Stops counting places once the Receiver is gone, releasing every
+    /// Sender that waits for one.
+    fn close_slots(&self) {
+        if let Some(ref slots) = self.slots {
+            *slots.free.lock().unwrap() = None;
+            slots.freed.notify_all();
         }
     }
 
-    /// Returns the old-style receiver along with the error.
-    /// The error will be None unless this channel was closed by an error.
-    pub fn into_inner(self) -> (mpsc::Receiver<CommMsg<T, E>>, Option<E>) {
-        let mut error_guard = self.error.write().unwrap();
-        (self.inner, error_guard.take())
+    /// Returns true if the waiters can't count on being woken when a
+    /// message arrives, so they have to wait inside the inner channel or
+    /// check it every `POLL_INTERVAL`.
+    fn polled(&self) -> bool {
+        self.rendezvous || self.untracked.load(Ordering::SeqCst)
     }
 
-    /// Returns the next message asyncrhonously.
-    ///
-    /// * If there is a message in the channels queue, it is returned in `Some`.
-    /// * If there is no message ready, None is returned.
-    /// * If the channel is closed, None is returned.
-    /// * If the channel is closed with an error, None is returned.
-    pub fn recv(&self) -> Option<T> {
-        if self.is_closed() {
-            return None
-        }
-        match self.inner.try_recv() {
-            Ok(CommMsg::Message(m)) => Some(m),
-            Ok(CommMsg::Error(e)) => {
-                * self.error.write().unwrap() = Some(e);
-                self.closed.set(true);
-                self.errored.set(true);
-                None
-            }
-            Err(mpsc::TryRecvError::Empty) => None,
-            Err(mpsc::TryRecvError::Disconnected) => {
-                self.closed.set(true);
-                None
-            }
-        }
+    /// Counts the calling thread as waiting until the returned guard is
+    /// dropped.  Anything that is sent after this wakes the thread.
+    fn start_waiting(&self) -> Waiting<'_> {
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        atomic::fence(Ordering::SeqCst);
+        Waiting(self)
     }
 
-    /// Returns the next message in the channe.  This method will block
-    /// until either a message arrives or the channel is closed
-    /// (either regularly) or by an error.
-    ///
-    /// * If a message arrives, the message is returned inside of `Some`.
-    /// * If the channel is closed, `None` is returned.
-    /// * If the channel is closed with an error, `None` is returned.
-    pub fn recv_block(&self) -> Option<T> {
-        if self.is_closed() {
-            return None
-        }
-        match self.inner.recv() {
-            Ok(CommMsg::Message(m)) => Some(m),
-            Ok(CommMsg::Error(e)) => {
-                * self.error.write().unwrap() = Some(e);
-                self.closed.set(true);
-                self.errored.set(true);
-                None
+    /// Wakes every thread waiting for something to change on this channel,
+    /// and the task waiting in `Receiver::poll_recv` if there is one.
+    fn wake(&self) {
+        // Pairs with the fence in `start_waiting`, so that either the
+        // waiter sees what changed or we see the waiter.
+        atomic::fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            self.notify.notify();
+            for w in self.watchers.lock().unwrap().iter() {
+                w.notify();
             }
-            Err(mpsc::RecvError) => {
-                self.closed.set(true);
-                None
+        }
+        #[cfg(feature = "futures")]
+        {
+            if let Some(w) = self.waker.lock().unwrap().take() {
+                w.wake();
             }
         }
     }
+}
 
-    /// Returns true if the channel was closed with an error.
-    pub fn has_error(&self) -> bool {
-        self.errored.get()
+impl Notify {
+    fn new() -> Notify {
+        Notify {
+            epoch: Mutex::new(0),
+            changed: Condvar::new()
+        }
     }
 
-    /// Returns the error if the channel was closed with an error.
-    /// This method moves the error out of the Receiver, so subsequent
-    /// calls will return None.
-    ///
-    /// Returns `None` if the channel wasn't closed with an error, or if
-    /// the error has already been taken.
-    pub fn take_error(&self) -> Option<E> {
-        self.errored.set(false);
-        self.error.write().unwrap().take()
+    fn epoch(&self) -> u64 {
+        *self.epoch.lock().unwrap()
     }
 
-    /// Returns true if the channel is closed.
-    pub fn is_closed(&self) -> bool {
-        self.closed.get()
+    fn notify(&self) {
+        *self.epoch.lock().unwrap() += 1;
+        self.changed.notify_all();
     }
 
-    /// Returns an iterator over the messages in this receiver.
-    /// The iterator is non-blocking, and borrows this receiver.
-    pub fn iter(&self) -> ReceiverIterator<T, E> {
-        ReceiverIterator {
-            blocking: false,
-            reference: MaybeOwned::Borrowed(self)
+    /// Blocks until `notify` has been called since `epoch` returned
+    /// `seen`, or until `deadline` has passed.
+    fn wait(&self, seen: u64, deadline: Option<Instant>) {
+        let mut epoch = self.epoch.lock().unwrap();
+        while *epoch == seen {
+            epoch = match deadline {
+                None => self.changed.wait(epoch).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return
+                    }
+                    self.changed.wait_timeout(epoch, deadline - now).unwrap().0
+                }
+            };
         }
     }
+}
 
-    /// Returns an iterator over the messages in this receiver.
-    /// The iterator is blocking and borrows this receiver.
-    pub fn blocking_iter(&self) -> ReceiverIterator<T, E> {
-        ReceiverIterator {
-            blocking: true,
-            reference: MaybeOwned::Borrowed(self)
+impl <'a> Watch<'a> {
+    /// Starts watching `channels`.  Anything that changes on them after
+    /// this wakes `wait`.
+    fn new(channels: Vec<&'a Shared>) -> Watch<'a> {
+        let notify = Arc::new(Notify::new());
+        for s in &channels {
+            s.watchers.lock().unwrap().push(notify.clone());
+            s.waiters.fetch_add(1, Ordering::SeqCst);
         }
+        atomic::fence(Ordering::SeqCst);
+        Watch { notify, channels }
     }
 
-    /// Returns an iterator over the messages in this receiver.
-    /// The iterator is non-blocking and consumes this receiver.
-    pub fn into_iter(self) -> ReceiverIterator<'static, T, E> {
-        ReceiverIterator {
-            blocking: false,
-            reference: MaybeOwned::Owned(self)
-        }
+    fn epoch(&self) -> u64 {
+        self.notify.epoch()
     }
 
-    /// Returns an iterator over the messages in this receiver.
-    /// The iterator is blocking, and consumes this receiver.
-    pub fn into_blocking_iter(self) -> ReceiverIterator<'static, T, E> {
-        ReceiverIterator {
-            blocking: true,
-            reference: MaybeOwned::Owned(self)
-        }
+    /// Blocks until something changed on one of the channels since
+    /// `epoch` returned `seen`.  Channels that have to be polled are
+    /// checked again after `POLL_INTERVAL` anyway.
+    fn wait(&self, seen: u64) {
+        let deadline = if self.channels.iter().any(|s| s.polled()) {
+            Some(Instant::now() + POLL_INTERVAL)
+        } else {
+            None
+        };
+        self.notify.wait(seen, deadline);
     }
 }
 
-impl <'a, T, E> Iterator for ReceiverIterator<'a, T, E>
+impl <'a> Drop for Watch<'a> {
+    fn drop(&mut self) {
+        for s in &self.channels {
+            s.watchers.lock().unwrap().retain(|w| !Arc::ptr_eq(w, &self.notify));
+            s.waiters.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+impl <'a> Drop for Waiting<'a> {
+    fn drop(&mut self) {
+        self.0.waiters.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+
+impl SenderShared {
+    fn new(shared: Arc<Shared>) -> SenderShared {
+        shared.senders.fetch_add(1, Ordering::SeqCst);
+        SenderShared(shared)
+    }
+
+    /// Makes `n` handles at once, counting them with a single update.
+    fn many(&self, n: usize) -> Vec<SenderShared> {
+        self.0.senders.fetch_add(n, Ordering::SeqCst);
+        (0..n).map(|_| SenderShared(self.0.clone())).collect()
+    }
+}
+
+impl Clone for SenderShared {
+    fn clone(&self) -> SenderShared {
+        SenderShared::new(self.0.clone())
+    }
+}
+
+impl Drop for SenderShared {
+    fn drop(&mut self) {
+        if self.0.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.wake();
+        }
+    }
+}
+
+impl Deref for SenderShared {
+    type Target = Shared;
+    fn deref(&self) -> &Shared {
+        &self.0
+    }
+}
+
+impl <'a, T: Send, E: Send> Permit<'a, T, E> {
+    /// Sends a message in the reserved place.  Returns an Err with the
+    /// message in the event that the sending surely failed.
+    ///
+    /// This doesn't block, unless a barrier or a close sent in the
+    /// meantime takes up the room in the queue.
+    pub fn send(mut self, t: T) -> Result<(), T> {
+        self.used = true;
+        match self.sender.send_acquired(CommMsg::Message(t)) {
+            Ok(()) => Ok(()),
+            Err(CommMsg::Message(t)) => Err(t),
+            Err(_) => unreachable!()
+        }
+    }
+}
+
+impl <'a, T: Send, E: Send> Drop for Permit<'a, T, E> {
+    fn drop(&mut self) {
+        if !self.used {
+            self.sender.shared.release_slot();
+        }
+    }
+}
+
+impl fmt::Display for ReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("reserving on a closed channel")
+    }
+}
+
+impl error::Error for ReserveError {}
+
+impl <T, E> io::Read for ChannelRead<T, E>
+where T: Send + AsRef<[u8]> + 'static, E: Send + Into<io::Error> + 'static {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        loop {
+            if let Some((ref chunk, ref mut pos)) = self.chunk {
+                let rest = &chunk.as_ref()[*pos..];
+                if !rest.is_empty() {
+                    let n = rest.len().min(buf.len());
+                    buf[..n].copy_from_slice(&rest[..n]);
+                    *pos += n;
+                    return Ok(n)
+                }
+            }
+            match self.receiver.recv_block() {
+                Some(chunk) => self.chunk = Some((chunk, 0)),
+                None => {
+                    self.chunk = None;
+                    return match self.receiver.take_error() {
+                        Some(e) => Err(e.into()),
+                        None => Ok(0)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl <'a, T: Send, E: Send> RecvGuard<'a, T, E> {
+    /// Marks the message as handled, so that it isn't put back, and
+    /// returns it.
+    pub fn ack(mut self) -> T {
+        self.msg.take().unwrap()
+    }
+}
+
+impl <'a, T: Send, E: Send> Deref for RecvGuard<'a, T, E> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.msg.as_ref().unwrap()
+    }
+}
+
+impl <'a, T: Send, E: Send> DerefMut for RecvGuard<'a, T, E> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.msg.as_mut().unwrap()
+    }
+}
+
+impl <'a, T: Send, E: Send> Drop for RecvGuard<'a, T, E> {
+    fn drop(&mut self) {
+        if let Some(m) = self.msg.take() {
+            self.receiver.front.lock().unwrap().push_front(m);
+            self.receiver.shared.wake();
+        }
+    }
+}
+
+impl Drop for ReceiverShared {
+    fn drop(&mut self) {
+        self.shared.close_slots();
+        if !self.detached {
+            self.shared.receiver_dropped.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Deref for ReceiverShared {
+    type Target = Shared;
+    fn deref(&self) -> &Shared {
+        &self.shared
+    }
+}
+
+impl <'a, A> MaybeOwned<'a, A> {
+    fn borrow<'b: 'a>(&'b self) -> &'b A  {
+        match *self {
+            MaybeOwned::Owned(ref a) => a,
+            MaybeOwned::Borrowed(a) => a
+        }
+    }
+}
+
+/// Returns a Sender-Receiver pair sending messages of type T, and
+/// can fail with an error of type E.
+pub fn channel<T, E>() -> (Sender<T, E>, Receiver<T, E>)
+where T: Send + 'static, E: Send + 'static{
+    let (tx, rx) = mpsc::channel();
+    pair(SenderKind::Unbounded(tx), rx, Shared::new())
+}
+
+/// Returns a Sender-Receiver pair like `channel`, with a hint of how many
+/// messages are expected to be queued at once.
+///
+/// The hint is advisory.  The queue of `std::sync::mpsc` allocates as it
+/// goes and can't be sized up front, so the hint only sizes the buffer the
+/// Receiver keeps for messages held back by `peek` and `unrecv`.
+pub fn channel_with_capacity_hint<T, E>(hint: usize) -> (Sender<T, E>, Receiver<T, E>)
+where T: Send + 'static, E: Send + 'static {
+    let (tx, mut rx) = channel();
+    rx.front.get_mut().unwrap().reserve(hint);
+    (tx, rx)
+}
+
+/// A Sender-Receiver pair for a channel that can never fail with an error.
+pub type MessageChannel<T> = (Sender<T, Infallible>, Receiver<T, Infallible>);
+
+/// Returns a Sender-Receiver pair sending messages of type T, which can
+/// never fail with an error.
+///
+/// No value of `Infallible` can be made, so `Sender::error` and
+/// `Sender::send_error` can't be called, and the channel only ends when it
+/// is closed.
+pub fn message_channel<T>() -> MessageChannel<T>
+where T: Send + 'static {
+    channel()
+}
+
+/// Returns a bounded Sender-Receiver pair sending messages of type T, and
+/// can fail with an error of type E.
+///
+/// At most `bound` messages (including an error) can be queued at once.
+/// Once the queue is full, `Sender::send` and `Sender::error` block until
+/// the receiver makes room.  A bound of 0 makes every send wait for the
+/// receiver to take the message.
+pub fn sync_channel<T, E>(bound: usize) -> (Sender<T, E>, Receiver<T, E>)
+where T: Send + 'static, E: Send + 'static {
+    let (tx, rx) = mpsc::sync_channel(bound);
+    let shared = if bound == 0 { Shared::rendezvous() } else { Shared::bounded(bound) };
+    pair(SenderKind::Bounded(tx), rx, shared)
+}
+
+/// Blocks until either `a` or `b` has a message ready, and returns it.
+///
+/// A receiver that is closed (regularly or by an error) is skipped, so
+/// the other one can still be received from.  Only once both are closed
+/// is `Left(None)` returned instead of waiting; an error can then be
+/// retrieved with `take_error`.  `a` is checked before `b`.
+///
+/// Senders wake the waiting thread when they send, except on channels with
+/// a bound of 0 and ones fed by a bare `std::sync::mpsc` sender (see
+/// `from_old` and `Sender::into_inner`), which are checked every
+/// millisecond instead.
+pub fn select2<T1, E1, T2, E2>(a: &Receiver<T1, E1>, b: &Receiver<T2, E2>)
+-> Either<Option<T1>, Option<T2>>
+where T1: Send + 'static, E1: Send + 'static, T2: Send + 'static, E2: Send + 'static {
+    let watch = Watch::new(vec![&a.shared, &b.shared]);
+    loop {
+        let seen = watch.epoch();
+        if let Some(m) = a.recv() {
+            return Either::Left(Some(m));
+        }
+        if let Some(m) = b.recv() {
+            return Either::Right(Some(m));
+        }
+        if a.is_closed() && b.is_closed() {
+            return Either::Left(None);
+        }
+        watch.wait(seen);
+    }
+}
+
+/// How long `Receiver::pull` may wait for the next message.
+enum Wait {
+    Never,
+    Forever,
+    Until(Instant)
+}
+
+fn timeout_error(e: mpsc::TryRecvError) -> mpsc::RecvTimeoutError {
+    match e {
+        mpsc::TryRecvError::Empty => mpsc::RecvTimeoutError::Timeout,
+        mpsc::TryRecvError::Disconnected => mpsc::RecvTimeoutError::Disconnected
+    }
+}
+
+/// Paces a polling loop: yields a few times, then sleeps for longer and
+/// longer, up to a millisecond at a time.
+struct Backoff {
+    step: u64
+}
+
+impl Backoff {
+    fn new() -> Backoff {
+        Backoff { step: 0 }
+    }
+
+    fn wait(&mut self) {
+        if self.step < 10 {
+            thread::yield_now();
+        } else {
+            thread::sleep(Duration::from_micros(self.step.min(1000)));
+        }
+        self.step += 1;
+    }
+}
+
+/// Returns a receiver that gets the messages of all of `receivers`.
+///
+/// One thread is spawned per input to forward its messages.  Messages from
+/// the same input arrive in the order they were sent, but there is no
+/// ordering between messages from different inputs.
+///
+/// The merged receiver is closed once every input has been closed.  If
+/// any input is closed with an error, that error is forwarded and closes
+/// the merged receiver, and can be retrieved with `take_error`.
+pub fn merge<T, E>(receivers: Vec<Receiver<T, E>>) -> Receiver<T, E>
+where T: Send + 'static, E: Send + 'static {
+    let (tx, rx) = channel();
+    for r in receivers {
+        let tx = tx.clone();
+        thread::spawn(move || forward(&r, &tx));
+    }
+    rx
+}
+
+/// Returns a receiver that gets the messages of all of `receivers`, taking
+/// one message from each input in turn.
+///
+/// Unlike `merge`, an input that sends a lot can't hold up the messages of
+/// the others: while several inputs have messages ready, they are served
+/// round-robin.  This costs throughput, since a single thread polls every
+/// input, backing off to sleeping for up to a millisecond while none of
+/// them is ready.
+///
+/// Closing and errors are handled the same way as `merge`.
+pub fn merge_fair<T, E>(receivers: Vec<Receiver<T, E>>) -> Receiver<T, E>
+where T: Send + 'static, E: Send + 'static {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let mut inputs = receivers;
+        let mut next = 0;
+        let mut backoff = Backoff::new();
+        while !inputs.is_empty() {
+            let mut progressed = false;
+            for k in 0..inputs.len() {
+                let i = (next + k) % inputs.len();
+                match inputs[i].recv_msg() {
+                    Some(m @ CommMsg::Message(_)) => {
+                        if tx.send_msg(m).is_err() {
+                            return;
+                        }
+                        next = i + 1;
+                    }
+                    Some(m @ CommMsg::Error(_)) => {
+                        let _ = tx.send_msg(m);
+                        return;
+                    }
+                    Some(CommMsg::Barrier(_)) | Some(CommMsg::CloseWith(_)) => unreachable!(),
+                    Some(CommMsg::Close) | None if inputs[i].is_closed() => {
+                        inputs.remove(i);
+                        next = i;
+                    }
+                    Some(CommMsg::Close) | None => continue
+                }
+                progressed = true;
+                break;
+            }
+            if progressed {
+                backoff = Backoff::new();
+            } else {
+                backoff.wait();
+            }
+        }
+    });
+    rx
+}
+
+/// Returns a receiver that pairs up the messages of `a` and `b`, so that
+/// the i-th message of each arrive together.
+///
+/// A thread is spawned that waits on both inputs at once, like `select2`,
+/// holding on to a message from one of them until the other sends its
+/// partner, and sends them as a pair.  The zipped receiver is closed as
+/// soon as an input is closed while no message of it is waiting for a
+/// partner, even if the other input stays open, and the inputs are
+/// dropped.  If the inputs have different lengths, a message that has no
+/// partner is dropped, as are any messages left in the longer input.  If
+/// an input is closed with an error, it is forwarded as `Either::Left` or
+/// `Either::Right` and closes the zipped receiver.
+pub fn zip<T1, E1, T2, E2>(a: Receiver<T1, E1>, b: Receiver<T2, E2>)
+-> Receiver<(T1, T2), ZipError<E1, E2>>
+where T1: Send + 'static, E1: Send + 'static, T2: Send + 'static, E2: Send + 'static {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let watch = Watch::new(vec![&a.shared, &b.shared]);
+        let mut x = None;
+        let mut y = None;
+        loop {
+            let seen = watch.epoch();
+            if x.is_none() {
+                match a.recv_checked() {
+                    Ok(Some(m)) => x = Some(m),
+                    Ok(None) if a.is_closed() => break,
+                    Ok(None) => {}
+                    Err(e) => {
+                        let _ = tx.error(Either::Left(e));
+                        return;
+                    }
+                }
+            }
+            if y.is_none() {
+                match b.recv_checked() {
+                    Ok(Some(m)) => y = Some(m),
+                    Ok(None) if b.is_closed() => break,
+                    Ok(None) => {}
+                    Err(e) => {
+                        let _ = tx.error(Either::Right(e));
+                        return;
+                    }
+                }
+            }
+            if x.is_some() && y.is_some() {
+                if tx.send((x.take().unwrap(), y.take().unwrap())).is_err() {
+                    break;
+                }
+                continue;
+            }
+            watch.wait(seen);
+        }
+    });
+    rx
+}
+
+/// Forwards messages and an error from `r` to `tx` until `r` is closed.
+/// A regular close is not forwarded.
+fn forward<T, E>(r: &Receiver<T, E>, tx: &Sender<T, E>)
+where T: Send + 'static, E: Send + 'static {
+    loop {
+        match r.recv_msg_block() {
+            Some(m @ CommMsg::Message(_)) => {
+                if tx.send_msg(m).is_err() {
+                    break;
+                }
+            }
+            Some(m @ CommMsg::Error(_)) => {
+                let _ = tx.send_msg(m);
+                break;
+            }
+            Some(CommMsg::Barrier(_)) | Some(CommMsg::CloseWith(_)) => unreachable!(),
+            Some(CommMsg::Close) | None => break
+        }
+    }
+}
+
+fn pair<T, E>(tx: SenderKind<T, E>, rx: mpsc::Receiver<CommMsg<T, E>>, shared: Arc<Shared>)
+-> (Sender<T, E>, Receiver<T, E>)
+where T: Send + 'static, E: Send + 'static {
+    (Sender::new(tx, SenderShared::new(shared.clone())), Receiver::new(rx, shared))
+}
+
+impl <T, E> Sender<T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Converts an old-stype Sender to a bchannel Sender.
+    pub fn from_old(v: mpsc::Sender<CommMsg<T, E>>) -> Sender<T, E> {
+        Sender::from(v)
+    }
+
+    /// Returns a Sender that silently discards everything sent through it,
+    /// without any Receiver behind it.
+    ///
+    /// Sending messages or errors to it always succeeds, and it never
+    /// reports `is_closed`.  This is useful for code that optionally
+    /// reports progress and would otherwise take an `Option<Sender>`.
+    pub fn null() -> Sender<T, E> {
+        Sender::new(SenderKind::Null, SenderShared::new(Shared::new()))
+    }
+
+    fn new(v: SenderKind<T, E>, shared: SenderShared) -> Sender<T, E> {
+        Sender {
+            closed: Cell::new(false),
+            label: None,
+            observer: None,
+            drop_error: None,
+            shared,
+            inner: Arc::new(v)
+        }
+    }
+
+    /// Returns the old-style Sender that is containd inside this Sender.
+    ///
+    /// A Sender created by `sync_channel` or `null` has no old-style Sender
+    /// inside, so it is handed back unchanged in `Err`.
+    pub fn into_inner(mut self) -> Result<mpsc::Sender<CommMsg<T, E>>, Sender<T, E>> {
+        match *self.inner {
+            SenderKind::Unbounded(ref s) => {
+                self.drop_error = None;
+                self.shared.untracked.store(true, Ordering::SeqCst);
+                Ok(s.clone())
+            }
+            SenderKind::Bounded(_) | SenderKind::Null => Err(self)
+        }
+    }
+
+    /// Attaches a label to this Sender, which shows up in its `Debug`
+    /// output.  Clones of this Sender keep the label.
+    pub fn with_label(mut self, label: &'static str) -> Sender<T, E> {
+        self.label = Some(label);
+        self
+    }
+
+    /// Returns the label attached with `with_label`.
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// Sets a callback that is told about every message and error this
+    /// Sender sends, every send that fails, and closing the channel with
+    /// `close` or `close_with`.  Clones of this Sender share the callback.
+    ///
+    /// This is meant for counting things like metrics in one place.  It
+    /// costs one indirect call per send, made on the sending thread, so
+    /// `f` should be cheap.  Dropping a Sender isn't reported, although an
+    /// error it sends because of `arm_drop_error` is.  Sends that find a
+    /// bounded channel full aren't reported either, since the message can
+    /// still be sent later: `send2`, `try_send` and `try_send_all` failing
+    /// with `Full`, and the attempts made by `send_timeout`.
+    pub fn with_observer<F>(mut self, f: F) -> Sender<T, E>
+    where F: Fn(SendEvent) + Send + Sync + 'static {
+        self.observer = Some(Arc::new(f));
+        self
+    }
+
+    /// Arms this Sender to send `e` when it is dropped without being closed
+    /// by `close`, `close_with` or `error`, e.g. because the producer
+    /// panicked.
+    ///
+    /// The receiver then sees the channel closed with `e`, so it can tell
+    /// an aborted producer from a finished one.  Clones of this Sender
+    /// aren't armed.  Taking the Sender apart with `into_inner` disarms it,
+    /// while `map` and `tee` keep it armed inside the new sending end, so
+    /// it fires if that is dropped without being closed.
+    ///
+    /// Dropping never blocks: if a bounded channel is full at that point,
+    /// or a channel with a bound of 0 has no receive waiting, the error is
+    /// discarded and the receiver only sees the channel disconnect.
+    pub fn arm_drop_error(mut self, e: E) -> Sender<T, E> {
+        self.drop_error = Some(e);
+        self
+    }
+
+    /// Reserves a place in the queue of a bounded channel, blocking until
+    /// there is room, so that the message can be sent later with
+    /// `Permit::send` without blocking.
+    ///
+    /// `std::sync::mpsc` can't hold places, so bounded channels count
+    /// their free places themselves, and every send takes one.  A permit
+    /// holds its place until it is used or dropped, so other sends can't
+    /// fill the channel in the meantime.  On an unbounded channel, and on
+    /// a channel with a bound of 0, this returns a permit straight away
+    /// and the permit sends like `send`.
+    ///
+    /// Returns `ReserveError` if the channel is closed.
+    pub fn reserve(&self) -> Result<Permit<'_, T, E>, ReserveError> {
+        if self.is_closed() {
+            return Err(ReserveError)
+        }
+        self.shared.acquire_slot();
+        if self.is_closed() {
+            self.shared.release_slot();
+            return Err(ReserveError)
+        }
+        Ok(Permit {
+            sender: self,
+            used: false
+        })
+    }
+
+    /// Returns true if this Sender and `other` send through the same
+    /// channel, e.g. because one is a clone of the other.
+    ///
+    /// Senders made separately with `from_old` are never considered the
+    /// same, even if they wrap the same `std::sync::mpsc` channel.
+    pub fn same_channel(&self, other: &Sender<T, E>) -> bool {
+        Arc::ptr_eq(&self.shared.0, &other.shared.0)
+    }
+
+    /// Returns true if the Receiver has asked the senders to stop with
+    /// `Receiver::request_close`.
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.shared.close_requested.load(Ordering::SeqCst)
+    }
+
+    /// Returns `n` clones of this Sender.
+    pub fn clones(&self, n: usize) -> Vec<Sender<T, E>> {
+        self.shared.many(n).into_iter().map(|shared| Sender {
+            closed: Cell::new(self.closed.get()),
+            label: self.label,
+            observer: self.observer.clone(),
+            drop_error: None,
+            shared,
+            inner: self.inner.clone()
+        }).collect()
+    }
+
+    /// Returns a weak handle on this Sender that doesn't keep the channel
+    /// open.
+    pub fn downgrade(&self) -> WeakSender<T, E> {
+        WeakSender {
+            label: self.label,
+            observer: self.observer.clone(),
+            shared: self.shared.0.clone(),
+            inner: Arc::downgrade(&self.inner)
+        }
+    }
+
+    /// Sends a message through the channel.  Returns `Ok(())` if the sending
+    /// might succeed, and returns an Err with the message that you tried to
+    /// send in the event that the sending surely failed.
+    ///
+    /// If the channel is bounded and full, this blocks until there is room.
+    ///
+    /// Every message costs more than sending through a bare
+    /// `std::sync::mpsc` channel, for keeping count of the queued messages
+    /// and waking the receiver.  `benches/send.rs` measures the difference
+    /// for small `Copy` messages, along with `send_batch`, which isn't any
+    /// cheaper per message since it sends them one at a time.
+    pub fn send(&self, t: T) -> Result<(), T> {
+        match self.send_msg(CommMsg::Message(t)) {
+            Ok(()) => Ok(()),
+            Err(CommMsg::Message(a)) => Err(a),
+            Err(_) => unreachable!()
+        }
+    }
+
+    /// Sends a message through the channel like `send`, but fails with a
+    /// `SendError`, which implements `std::error::Error`.
+    pub fn send_err(&self, t: T) -> Result<(), SendError<T>> {
+        self.send(t).map_err(SendError)
+    }
+
+    /// Sends a message through the channel without blocking, and says why
+    /// the sending failed.
+    ///
+    /// If an earlier send on this Sender already failed, the message is
+    /// handed back in `SendErr::Closed` without trying to send it.  If a
+    /// bounded channel is full, it is handed back in `SendErr::Full`, and if
+    /// the receiving end turns out to be gone, in `SendErr::Disconnected`.
+    pub fn try_send(&self, t: T) -> Result<(), SendErr<T>> {
+        if self.closed.get() {
+            return Err(SendErr::Closed(t))
+        }
+        self.send2(t).map_err(|e| match e {
+            TrySendError::Full(t) => SendErr::Full(t),
+            TrySendError::Disconnected(t) => SendErr::Disconnected(t)
+        })
+    }
+
+    /// Sends a message through the channel without blocking, and says why
+    /// the sending failed.
+    ///
+    /// This is meant to replace `send` in the next major version, so that
+    /// bounded and unbounded channels fail the same way.  If a bounded
+    /// channel is full, the message is handed back in `TrySendError::Full`
+    /// and the Sender stays usable.  If the receiving end is gone, it is
+    /// handed back in `TrySendError::Disconnected`.  Use `into_inner` to
+    /// get the `Err(T)` that `send` returns.
+    pub fn send2(&self, t: T) -> Result<(), TrySendError<T>> {
+        match self.try_send_msg(CommMsg::Message(t)) {
+            Ok(()) => Ok(()),
+            Err(mpsc::TrySendError::Full(CommMsg::Message(t))) => Err(TrySendError::Full(t)),
+            Err(mpsc::TrySendError::Disconnected(CommMsg::Message(t))) => Err(TrySendError::Disconnected(t)),
+            Err(_) => unreachable!()
+        }
+    }
+
+    /// Sends a message through the channel, waiting at most `dur` for room
+    /// in a bounded channel.  Returns the message back in
+    /// `SendTimeoutError::Timeout` if the channel stayed full, or in
+    /// `SendTimeoutError::Disconnected` if the sending surely failed.
+    ///
+    /// `std::sync::mpsc` can't wait for room with a timeout, so this polls
+    /// the channel until there is room.  On an unbounded channel this
+    /// behaves like `send` and never times out.
+    pub fn send_timeout(&self, t: T, dur: Duration) -> Result<(), SendTimeoutError<T>> {
+        let deadline = Instant::now() + dur;
+        let mut backoff = Backoff::new();
+        let mut msg = CommMsg::Message(t);
+        loop {
+            match self.try_send_msg(msg) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::TrySendError::Full(m)) => msg = m,
+                Err(mpsc::TrySendError::Disconnected(CommMsg::Message(t))) => {
+                    return Err(SendTimeoutError::Disconnected(t))
+                }
+                Err(_) => unreachable!()
+            }
+            if Instant::now() >= deadline {
+                match msg {
+                    CommMsg::Message(t) => return Err(SendTimeoutError::Timeout(t)),
+                    _ => unreachable!()
+                }
+            }
+            backoff.wait();
+        }
+    }
+
+    /// Tries to send all of the messages in an iterator.  Returns Ok(()) if the
+    /// sending might succeed and returns Err with a tuple containing the message
+    /// that failed to send, and the remaining iterator.
+    pub fn send_all<I: Iterator<Item=T>>(&self, mut i: I) -> Result<(), (T, I)> {
+        loop {
+            match i.next() {
+                None => break,
+                Some(x) => {
+                    match self.send(x) {
+                        Ok(()) => {}
+                        Err(x) => return Err((x, i))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Tries to send all of the messages in an iterator without blocking.
+    ///
+    /// This stops at the first message that can't be sent right away and
+    /// returns it along with the remaining iterator, in `SendAllStop::Full`
+    /// if the channel is full and in `SendAllStop::Disconnected` if the
+    /// sending surely failed.  On an unbounded channel this never stops
+    /// with `Full`.
+    pub fn try_send_all<I: Iterator<Item=T>>(&self, mut i: I) -> Result<(), SendAllStop<T, I>> {
+        while let Some(x) = i.next() {
+            match self.try_send_msg(CommMsg::Message(x)) {
+                Ok(()) => {}
+                Err(mpsc::TrySendError::Full(CommMsg::Message(x))) => {
+                    return Err(SendAllStop::Full(x, i))
+                }
+                Err(mpsc::TrySendError::Disconnected(CommMsg::Message(x))) => {
+                    return Err(SendAllStop::Disconnected(x, i))
+                }
+                Err(_) => unreachable!()
+            }
+        }
+        Ok(())
+    }
+
+    /// Tries to send all of the messages in an iterator, counting how many
+    /// were sent.  Returns Ok with the number of messages sent if the
+    /// sending might succeed, and returns Err with a tuple containing the
+    /// number of messages sent before the failure, the message that failed
+    /// to send, and the remaining iterator.
+    pub fn send_all_counted<I: Iterator<Item=T>>(&self, mut i: I) -> Result<usize, (usize, T, I)> {
+        let mut sent = 0;
+        while let Some(x) = i.next() {
+            match self.send(x) {
+                Ok(()) => sent += 1,
+                Err(x) => return Err((sent, x, i))
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Sends every message in `items`, in order, leaving `items` empty.
+    ///
+    /// If the sending surely failed, the messages that weren't sent are
+    /// left in `items` and the number of messages that were sent is
+    /// returned in `Err`.
+    pub fn send_batch(&self, items: &mut Vec<T>) -> Result<(), usize> {
+        let batch = mem::take(items);
+        match self.send_all_counted(batch.into_iter()) {
+            Ok(_) => Ok(()),
+            Err((sent, x, rest)) => {
+                items.push(x);
+                items.extend(rest);
+                Err(sent)
+            }
+        }
+    }
+
+    /// Closes the sending end of the channel.
+    ///
+    /// This sends an explicit end marker after any messages that have
+    /// already been sent, so the receiver still gets those messages first.
+    /// Once the receiver reaches the marker it sees the channel as closed,
+    /// and a blocking receive or iterator stops, even if other clones of
+    /// this Sender are still alive.
+    ///
+    /// Closing one clone therefore closes the channel for all of them:
+    /// messages sent by other clones after the marker are never received,
+    /// although their `send` may still report success.  The receiver can't
+    /// tell which clone closed the channel.  Dropping a Sender without
+    /// calling `close` only closes the channel once every clone is gone.
+    ///
+    /// On a bounded channel, this blocks until there is room for the
+    /// marker.
+    pub fn close(mut self) {
+        self.drop_error = None;
+        let _ = self.send_msg(CommMsg::Close);
+    }
+
+    /// Closes the sending end of the channel like `close`, with a value
+    /// saying why.
+    ///
+    /// This is for graceful closes that aren't errors: the receiver sees
+    /// the channel as closed regularly, without an error, and can retrieve
+    /// `reason` with `Receiver::take_close_value`.
+    pub fn close_with(mut self, reason: E) {
+        self.drop_error = None;
+        let _ = self.send_msg(CommMsg::CloseWith(reason));
+    }
+
+    /// Sends a barrier through the channel, returning a token that can wait
+    /// until the receiver has gone past it.
+    ///
+    /// The barrier is queued after every message this Sender has already
+    /// sent.  It only completes while the receiver is actively receiving:
+    /// either one of the receiving methods or an iterator has to reach it.
+    /// Receivers created by `map`, `filter` and `merge` reach it as soon as
+    /// their forwarding thread does.
+    pub fn barrier(&self) -> BarrierToken {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.send_msg(CommMsg::Barrier(BarrierSignal(tx)));
+        BarrierToken(rx)
+    }
+
+    /// Closes the sending end of the channel with an error.
+    pub fn error(mut self, e: E) -> Result<(), E> {
+        self.drop_error = None;
+        self.send_error(e)
+    }
+
+    /// Sends an error through the channel without consuming this Sender.
+    /// Returns an Err with the error in the event that the sending surely
+    /// failed.
+    ///
+    /// This makes it possible to send several errors through one channel.
+    /// Note that the receiver treats the first error it sees as closing the
+    /// channel unless it collects errors (see
+    /// `Receiver::set_collect_errors`).
+    pub fn send_error(&self, e: E) -> Result<(), E> {
+        match self.send_msg(CommMsg::Error(e)) {
+            Ok(()) => Ok(()),
+            Err(CommMsg::Error(a)) => Err(a),
+            Err(_) => unreachable!()
+        }
+    }
+
+    /// Sends `Ok` values as a message and `Err` values as an error.
+    /// Returns an Err with the payload that you tried to send in the event
+    /// that the sending surely failed.
+    ///
+    /// Like `send_error`, this doesn't consume the Sender when sending an
+    /// error.
+    pub fn send_result(&self, r: Result<T, E>) -> Result<(), SendResultError<T, E>> {
+        match r {
+            Ok(t) => self.send(t).map_err(SendResultError::Message),
+            Err(e) => self.send_error(e).map_err(SendResultError::Error)
+        }
+    }
+
+    /// Returns true if any message has failed to send, or if the Receiver
+    /// has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.closed.get() || self.shared.receiver_dropped.load(Ordering::SeqCst)
+    }
+
+    /// Returns a Sender that calls `f` on every message before sending it
+    /// through this channel.  Errors are sent through unchanged.
+    pub fn map<U, F>(self, f: F) -> MappedSender<U, T, E, F>
+    where F: Fn(U) -> T {
+        MappedSender {
+            sender: self,
+            f,
+            _marker: PhantomData
+        }
+    }
+
+    /// Returns a Sender that sends a copy of every message through both
+    /// this channel and `other`.
+    pub fn tee(self, other: Sender<T, E>) -> TeeSender<T, E>
+    where T: Clone {
+        TeeSender {
+            first: self,
+            second: other
+        }
+    }
+}
+
+impl <T: Send, E: Send> Sender<T, E> {
+    /// Sends a raw message through the channel, keeping the queued message
+    /// count and the closed state up to date.
+    fn send_msg(&self, m: CommMsg<T, E>) -> Result<(), CommMsg<T, E>> {
+        if let CommMsg::Message(_) | CommMsg::Error(_) = m {
+            self.shared.acquire_slot();
+        }
+        self.send_acquired(m)
+    }
+
+    /// Like `send_msg`, for a message that already has a place in the
+    /// queue of a bounded channel.
+    fn send_acquired(&self, m: CommMsg<T, E>) -> Result<(), CommMsg<T, E>> {
+        let event = match m {
+            CommMsg::Message(_) => Some(SendEvent::Sent),
+            CommMsg::Error(_) => Some(SendEvent::Errored),
+            CommMsg::Close | CommMsg::CloseWith(_) => Some(SendEvent::Closed),
+            CommMsg::Barrier(_) => None
+        };
+        let counted = match m {
+            CommMsg::Message(_) | CommMsg::Error(_) => true,
+            CommMsg::Close | CommMsg::CloseWith(_) | CommMsg::Barrier(_) => false
+        };
+        if counted {
+            self.shared.len.fetch_add(1, Ordering::SeqCst);
+        }
+        match self.inner.send(m) {
+            Ok(()) => {
+                self.shared.wake();
+                self.observe(event, true);
+                Ok(())
+            }
+            Err(mpsc::SendError(m)) => {
+                if counted {
+                    self.shared.len.fetch_sub(1, Ordering::SeqCst);
+                    self.shared.release_slot();
+                }
+                self.closed.set(true);
+                self.observe(event, false);
+                Err(m)
+            }
+        }
+    }
+
+    /// Like `send_msg`, but fails instead of blocking when a bounded channel
+    /// is full.  Only a disconnected channel marks this Sender as closed.
+    fn try_send_msg(&self, m: CommMsg<T, E>) -> Result<(), mpsc::TrySendError<CommMsg<T, E>>> {
+        let event = match m {
+            CommMsg::Message(_) => Some(SendEvent::Sent),
+            CommMsg::Error(_) => Some(SendEvent::Errored),
+            CommMsg::Close | CommMsg::CloseWith(_) => Some(SendEvent::Closed),
+            CommMsg::Barrier(_) => None
+        };
+        let counted = match m {
+            CommMsg::Message(_) | CommMsg::Error(_) => true,
+            CommMsg::Close | CommMsg::CloseWith(_) | CommMsg::Barrier(_) => false
+        };
+        if counted {
+            if !self.shared.try_acquire_slot() {
+                return Err(mpsc::TrySendError::Full(m))
+            }
+            self.shared.len.fetch_add(1, Ordering::SeqCst);
+        }
+        match self.inner.try_send(m) {
+            Ok(()) => {
+                self.shared.wake();
+                self.observe(event, true);
+                Ok(())
+            }
+            Err(err) => {
+                if counted {
+                    self.shared.len.fetch_sub(1, Ordering::SeqCst);
+                    self.shared.release_slot();
+                }
+                if let mpsc::TrySendError::Disconnected(_) = err {
+                    self.closed.set(true);
+                    self.observe(event, false);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Reports `event` to the observer, if there is one.
+    fn observe(&self, event: Option<SendEvent>, ok: bool) {
+        if let (Some(ref f), Some(event)) = (&self.observer, event) {
+            match event {
+                SendEvent::Sent | SendEvent::Errored if !ok => f(SendEvent::Failed),
+                event => f(event)
+            }
+        }
+    }
+}
+
+impl <T: Send, E: Send> Drop for Sender<T, E> {
+    fn drop(&mut self) {
+        // Dropping must not block, since the producer may be unwinding
+        // while the consumer joins it instead of receiving.
+        if let Some(e) = self.drop_error.take() {
+            let _ = self.try_send_msg(CommMsg::Error(e));
+        }
+    }
+}
+
+impl <U, T, E, F> MappedSender<U, T, E, F>
+where T: Send + 'static, E: Send + 'static, F: Fn(U) -> T {
+    /// Returns the Sender that this MappedSender sends through.
+    pub fn into_inner(self) -> Sender<T, E> {
+        self.sender
+    }
+
+    /// Transforms a message and sends it through the channel.  Returns
+    /// `Ok(())` if the sending might succeed, and returns an Err with the
+    /// transformed message in the event that the sending surely failed.
+    pub fn send(&self, u: U) -> Result<(), T> {
+        self.sender.send((self.f)(u))
+    }
+
+    /// Closes the sending end of the channel.
+    pub fn close(self) {
+        self.sender.close()
+    }
+
+    /// Closes the sending end of the channel with an error.
+    pub fn error(self, e: E) -> Result<(), E> {
+        self.sender.error(e)
+    }
+
+    /// Returns true if any message has failed to send, or if the Receiver
+    /// has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+impl <T, E> TeeSender<T, E>
+where T: Send + Clone + 'static, E: Send + 'static {
+    /// Returns the two Senders that this TeeSender sends through.
+    pub fn into_inner(self) -> (Sender<T, E>, Sender<T, E>) {
+        (self.first, self.second)
+    }
+
+    /// Sends the message through both channels.  Returns `Ok(())` if the
+    /// sending might succeed on at least one of them, and returns an Err
+    /// with the message only if the sending surely failed on both.
+    pub fn send(&self, t: T) -> Result<(), T> {
+        let first = self.first.send(t.clone());
+        match self.second.send(t) {
+            Err(t) if first.is_err() => Err(t),
+            _ => Ok(())
+        }
+    }
+
+    /// Closes both channels.
+    pub fn close(self) {
+        self.first.close();
+        self.second.close();
+    }
+
+    /// Closes both channels with an error.  Returns an Err with the error
+    /// only if the sending surely failed on both.
+    pub fn error(self, e: E) -> Result<(), E>
+    where E: Clone {
+        let first = self.first.error(e.clone());
+        match self.second.error(e) {
+            Err(e) if first.is_err() => Err(e),
+            _ => Ok(())
+        }
+    }
+
+    /// Returns true if both channels are closed.
+    pub fn is_closed(&self) -> bool {
+        self.first.is_closed() && self.second.is_closed()
+    }
+}
+
+impl <U, T, E, F> Clone for MappedSender<U, T, E, F>
+where T: Send + 'static, E: Send + 'static, F: Clone {
+    fn clone(&self) -> MappedSender<U, T, E, F> {
+        MappedSender {
+            sender: self.sender.clone(),
+            f: self.f.clone(),
+            _marker: PhantomData
+        }
+    }
+}
+
+impl <T, E> Broadcaster<T, E>
+where T: Send + Clone + 'static, E: Send + Clone + 'static {
+    /// Returns a Broadcaster with no subscribers.
+    pub fn new() -> Broadcaster<T, E> {
+        Broadcaster {
+            senders: Mutex::new(Vec::new())
+        }
+    }
+
+    /// Returns a receiver that gets every message sent after this call.
+    pub fn subscribe(&self) -> Receiver<T, E> {
+        let (tx, rx) = channel();
+        self.senders.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Sends a copy of the message to every subscriber.  Subscribers whose
+    /// receiver has been dropped are removed.
+    ///
+    /// Returns the number of subscribers that the message was sent to.
+    pub fn send(&self, t: T) -> usize {
+        let mut senders = self.senders.lock().unwrap();
+        senders.retain(|s| s.send(t.clone()).is_ok());
+        senders.len()
+    }
+
+    /// Closes every subscriber's channel with an error.  Subscribers that
+    /// subscribe after this call are unaffected.
+    pub fn error(&self, e: E) {
+        for s in self.senders.lock().unwrap().drain(..) {
+            let _ = s.error(e.clone());
+        }
+    }
+
+    /// Closes every subscriber's channel.  Subscribers that subscribe after
+    /// this call are unaffected.
+    pub fn close(&self) {
+        for s in self.senders.lock().unwrap().drain(..) {
+            s.close();
+        }
+    }
+}
+
+impl <T, E> Default for Broadcaster<T, E>
+where T: Send + Clone + 'static, E: Send + Clone + 'static {
+    fn default() -> Broadcaster<T, E> {
+        Broadcaster::new()
+    }
+}
+
+impl <T, E> WeakSender<T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Returns a Sender for the channel, or None if every Sender is gone.
+    pub fn upgrade(&self) -> Option<Sender<T, E>> {
+        self.inner.upgrade().map(|inner| Sender {
+            closed: Cell::new(false),
+            label: self.label,
+            observer: self.observer.clone(),
+            drop_error: None,
+            shared: SenderShared::new(self.shared.clone()),
+            inner
+        })
+    }
+}
+
+impl <T: Send, E: Send> Clone for WeakSender<T, E> {
+    fn clone(&self) -> WeakSender<T, E> {
+        WeakSender {
+            label: self.label,
+            observer: self.observer.clone(),
+            shared: self.shared.clone(),
+            inner: self.inner.clone()
+        }
+    }
+}
+
+impl <T> SendError<T> {
+    /// Returns the message that failed to send.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl <T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl <T: fmt::Debug> error::Error for SendError<T> {}
+
+impl <T> TrySendError<T> {
+    /// Returns the message that failed to send.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(t) | TrySendError::Disconnected(t) => t
+        }
+    }
+}
+
+impl <T> From<mpsc::TrySendError<T>> for TrySendError<T> {
+    fn from(e: mpsc::TrySendError<T>) -> TrySendError<T> {
+        match e {
+            mpsc::TrySendError::Full(t) => TrySendError::Full(t),
+            mpsc::TrySendError::Disconnected(t) => TrySendError::Disconnected(t)
+        }
+    }
+}
+
+impl <T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrySendError::Full(_) => f.write_str("sending on a full channel"),
+            TrySendError::Disconnected(_) => f.write_str("sending on a closed channel")
+        }
+    }
+}
+
+impl <T: fmt::Debug> error::Error for TrySendError<T> {}
+
+impl BarrierSignal {
+    /// Completes the matching `BarrierToken`.
+    pub fn fire(self) {
+        let _ = self.0.send(());
+    }
+}
+
+impl PartialEq for BarrierSignal {
+    fn eq(&self, _: &BarrierSignal) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for BarrierSignal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("BarrierSignal")
+    }
+}
+
+impl BarrierToken {
+    /// Blocks until the receiver has gone past the barrier.
+    ///
+    /// Returns true if the receiver reached the barrier, and false if the
+    /// barrier was dropped without being reached, e.g. because the
+    /// receiver is gone.
+    pub fn wait(self) -> bool {
+        self.0.recv().is_ok()
+    }
+}
+
+impl CancelToken {
+    /// Creates a token that hasn't been cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancels every receive waiting on this token or any of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if `cancel` has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl <T, E> From<mpsc::Sender<CommMsg<T, E>>> for Sender<T, E>
+where T: Send + 'static, E: Send + 'static {
+    fn from(v: mpsc::Sender<CommMsg<T, E>>) -> Sender<T, E> {
+        Sender::new(SenderKind::Unbounded(v), SenderShared::new(Shared::new()))
+    }
+}
+
+/// Equivalent to `Sender::into_inner`, so a Sender created by
+/// `sync_channel` or `null` is handed back in `Err`.
+impl <T, E> TryFrom<Sender<T, E>> for mpsc::Sender<CommMsg<T, E>>
+where T: Send + 'static, E: Send + 'static {
+    type Error = Sender<T, E>;
+
+    fn try_from(v: Sender<T, E>) -> Result<mpsc::Sender<CommMsg<T, E>>, Sender<T, E>> {
+        v.into_inner()
+    }
+}
+
+impl <T, E> Clone for Sender<T, E>
+where T: Send + 'static, E: Send + 'static {
+    fn clone(&self) -> Sender<T, E> {
+        Sender {
+            inner: self.inner.clone(),
+            shared: self.shared.clone(),
+            closed: Cell::new(self.closed.get()),
+            label: self.label,
+            observer: self.observer.clone(),
+            drop_error: None
+        }
+    }
+}
+
+
+impl <T, E> Receiver<T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Converts an old-style receiver to a bchannel receiver.
+    pub fn from_old(v: mpsc::Receiver<CommMsg<T, E>>) -> Receiver<T, E> {
+        Receiver::from(v)
+    }
+
+    fn new(v: mpsc::Receiver<CommMsg<T, E>>, shared: Arc<Shared>) -> Receiver<T, E> {
+        Receiver {
+            closed: AtomicBool::new(false),
+            label: None,
+            errored: AtomicBool::new(false),
+            closed_by_error: AtomicBool::new(false),
+            ended: AtomicBool::new(false),
+            collect_errors: AtomicBool::new(false),
+            drain_on_error: AtomicBool::new(true),
+            errors: RwLock::new(Vec::new()),
+            shared: ReceiverShared { shared, detached: false },
+            pending_error: Mutex::new(None),
+            shared_error: Mutex::new(None),
+            close_value: Mutex::new(None),
+            front: Mutex::new(VecDeque::new()),
+            inner: Mutex::new(v)
+        }
+    }
+
+    /// Attaches a label to this Receiver, which shows up in its `Debug`
+    /// output.
+    pub fn with_label(mut self, label: &'static str) -> Receiver<T, E> {
+        self.label = Some(label);
+        self
+    }
+
+    /// Returns the label attached with `with_label`.
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// Returns the old-style receiver along with the error.
+    /// The error will be None unless this channel was closed by an error.
+    ///
+    /// Messages held back by `peek`, `poll_error` or `unrecv` are
+    /// discarded; use `into_parts` to keep them.
+    pub fn into_inner(self) -> (mpsc::Receiver<CommMsg<T, E>>, Option<E>) {
+        let (inner, error, _) = self.into_parts();
+        (inner, error)
+    }
+
+    /// Returns the old-style receiver along with the error and the messages
+    /// that were held back by `peek`, `poll_error` or `unrecv`.
+    ///
+    /// The held back messages are in the order they would have been
+    /// received, and come before anything that is still queued in the
+    /// old-style receiver.
+    pub fn into_parts(mut self) -> (mpsc::Receiver<CommMsg<T, E>>, Option<E>, Vec<T>) {
+        let error = self.take_error().or_else(|| self.pending_error.lock().unwrap().take());
+        let front = self.front.lock().unwrap().drain(..).collect();
+        self.shared.detached = true;
+        (self.inner.into_inner().unwrap(), error, front)
+    }
+
+    /// Returns the next message asyncrhonously.
+    ///
+    /// * If there is a message in the channels queue, it is returned in `Some`.
+    /// * If there is no message ready, None is returned.
+    /// * If the channel is closed, None is returned.
+    /// * If the channel is closed with an error, None is returned.
+    ///
+    /// Messages that were queued behind an error are still returned before
+    /// the channel closes, and so are the messages that were queued when
+    /// the last Sender was dropped.
+    pub fn recv(&self) -> Option<T> {
+        self.next_msg(Wait::Never, true).and_then(|m| self.unwrap_msg(m))
+    }
+
+    /// Receives the next message asynchronously and lends it to `f`,
+    /// returning whatever `f` returns.  The message is dropped once `f`
+    /// is done with it.
+    ///
+    /// Closing and errors are handled the same way as `recv`: if there is
+    /// no message, `f` is not called and None is returned.
+    pub fn recv_with<R, F: FnOnce(&T) -> R>(&self, f: F) -> Option<R> {
+        self.recv().map(|m| f(&m))
+    }
+
+    /// Returns the next message asynchronously, with an error that closes
+    /// the channel returned in `Err`.
+    ///
+    /// * If there is a message in the channels queue, it is returned in
+    ///   `Ok(Some)`.
+    /// * If there is no message ready, `Ok(None)` is returned.
+    /// * If the channel is closed, `Ok(None)` is returned.
+    /// * If the channel is closed with an error, the error is returned in
+    ///   `Err` the first time it is seen.  It is not stored for
+    ///   `take_error`.
+    pub fn recv_checked(&self) -> Result<Option<T>, E> {
+        Receiver::check(self.recv_msg())
+    }
+
+    /// Returns the next message in the channel, with an error that closes
+    /// the channel returned in `Err`.  This method will block until either
+    /// a message arrives or the channel is closed.
+    ///
+    /// Closing and errors are handled the same way as `recv_checked`.
+    pub fn recv_checked_block(&self) -> Result<Option<T>, E> {
+        Receiver::check(self.recv_msg_block())
+    }
+
+    /// Returns the next message in the channel, with an error that closes
+    /// the channel returned in `Err`.  This method will block until either
+    /// a message arrives or the channel is closed.
+    ///
+    /// This is the same as `recv_checked_block`, and is named after
+    /// `recv_block`.  After it returns `Err`, `is_closed` returns true and
+    /// `close_reason` returns `CloseReason::Error`.
+    pub fn recv_block_checked(&self) -> Result<Option<T>, E> {
+        self.recv_checked_block()
+    }
+
+    fn check(msg: Option<CommMsg<T, E>>) -> Result<Option<T>, E> {
+        match msg {
+            Some(CommMsg::Message(m)) => Ok(Some(m)),
+            Some(CommMsg::Error(e)) => Err(e),
+            Some(CommMsg::Barrier(_)) | Some(CommMsg::CloseWith(_)) => unreachable!(),
+            Some(CommMsg::Close) | None => Ok(None)
+        }
+    }
+
+    /// Returns the next message asynchronously, reporting the state of the
+    /// channel alongside it.
+    ///
+    /// * If there is a message in the channels queue, it is returned in
+    ///   `RecvStatus::Message`.
+    /// * If there is no message ready, `RecvStatus::Empty` is returned.
+    /// * If the channel is closed, `RecvStatus::Closed` is returned.
+    /// * If the channel is closed with an error, the error is moved out of
+    ///   the receiver and returned in `RecvStatus::Errored`.
+    pub fn try_recv(&self) -> RecvStatus<T, E> {
+        if let Some(m) = self.front.lock().unwrap().pop_front() {
+            return RecvStatus::Message(m)
+        }
+        if self.is_closed() {
+            return match self.take_error() {
+                Some(e) => RecvStatus::Errored(e),
+                None => RecvStatus::Closed
+            }
+        }
+        match self.recv_msg() {
+            Some(CommMsg::Message(m)) => RecvStatus::Message(m),
+            Some(CommMsg::Error(e)) => RecvStatus::Errored(e),
+            Some(CommMsg::Close) => RecvStatus::Closed,
+            Some(CommMsg::Barrier(_)) | Some(CommMsg::CloseWith(_)) => unreachable!(),
+            None if self.is_closed() => RecvStatus::Closed,
+            None => RecvStatus::Empty
+        }
+    }
+
+    /// Returns the next message in the channe.  This method will block
+    /// until either a message arrives or the channel is closed
+    /// (either regularly) or by an error.
+    ///
+    /// * If a message arrives, the message is returned inside of `Some`.
+    /// * If the channel is closed, `None` is returned.
+    /// * If the channel is closed with an error, `None` is returned.
+    pub fn recv_block(&self) -> Option<T> {
+        self.next_msg(Wait::Forever, true).and_then(|m| self.unwrap_msg(m))
+    }
+
+    /// Returns the next message in the channel, blocking until one arrives,
+    /// with the same signature as `std::sync::mpsc::Receiver::recv`.
+    ///
+    /// This behaves like `recv_block`, with `RecvError` standing in for
+    /// `None`.  A channel closed with an error looks like a disconnected
+    /// one; the error is stored and can be retrieved with `take_error`.
+    pub fn std_recv(&self) -> Result<T, mpsc::RecvError> {
+        self.recv_block().ok_or(mpsc::RecvError)
+    }
+
+    /// Returns the next message asynchronously, with the same signature as
+    /// `std::sync::mpsc::Receiver::try_recv`.
+    ///
+    /// This behaves like `recv`, returning `TryRecvError::Empty` if there
+    /// is no message ready and `TryRecvError::Disconnected` if the channel
+    /// is closed.  A channel closed with an error looks like a disconnected
+    /// one; the error is stored and can be retrieved with `take_error`.
+    pub fn std_try_recv(&self) -> Result<T, mpsc::TryRecvError> {
+        match self.recv() {
+            Some(t) => Ok(t),
+            None if self.is_closed() => Err(mpsc::TryRecvError::Disconnected),
+            None => Err(mpsc::TryRecvError::Empty)
+        }
+    }
+
+    /// Returns the next message in the channel, spinning before it blocks.
+    ///
+    /// This checks the queue up to `spins` times, busy-waiting in between,
+    /// and only falls back to blocking like `recv_block` if no message has
+    /// arrived by then.  This can cut latency when messages come in bursts,
+    /// at the cost of burning CPU while spinning.  Closing and errors are
+    /// handled the same way as `recv_block`.
+    pub fn recv_spin(&self, spins: usize) -> Option<T> {
+        for _ in 0..spins {
+            match self.recv() {
+                Some(m) => return Some(m),
+                None if self.is_closed() => return None,
+                None => hint::spin_loop()
+            }
+        }
+        self.recv_block()
+    }
+
+    /// Returns the next message in the channel.  This method will block
+    /// until either a message arrives, the channel is closed, or `dur` has
+    /// elapsed.
+    ///
+    /// * If a message arrives, the message is returned inside of `Some`.
+    /// * If the timeout elapses, `None` is returned.  The channel is not
+    ///   marked as closed.
+    /// * If the channel is closed, `None` is returned.
+    /// * If the channel is closed with an error, `None` is returned.
+    pub fn recv_timeout(&self, dur: Duration) -> Option<T> {
+        self.recv_deadline(Instant::now() + dur)
+    }
+
+    /// Returns the next message in the channel, blocking like `recv_block`
+    /// until a message arrives, the channel is closed, or `token` is
+    /// cancelled.
+    ///
+    /// Cancelling the token doesn't wake a blocked receive, so this
+    /// waits in slices of 10ms and checks the token in between; a cancel
+    /// is noticed within about 10ms.  Once cancelled, `None` is returned
+    /// without receiving anything and the channel is not marked as closed,
+    /// so `is_closed` tells a cancel apart from a close.
+    pub fn recv_block_cancellable(&self, token: &CancelToken) -> Option<T> {
+        while !token.is_cancelled() {
+            match self.recv_timeout(CANCEL_POLL) {
+                Some(m) => return Some(m),
+                None if self.is_closed() => return None,
+                None => {}
+            }
+        }
+        None
+    }
+
+    /// Returns the next message in the channel, blocking like
+    /// `recv_timeout`, but says why no message was returned.
+    ///
+    /// * If the timeout elapses, `RecvTimeoutError::Timeout` is returned.
+    /// * If the channel is closed, `RecvTimeoutError::Disconnected` is
+    ///   returned.
+    /// * If the channel is closed with an error, the error is returned in
+    ///   `RecvTimeoutError::Errored` the first time it is seen.  It is not
+    ///   stored for `take_error`, and later calls return `Disconnected`.
+    pub fn recv_timeout_raw(&self, dur: Duration) -> Result<T, RecvTimeoutError<E>> {
+        match self.pull(Wait::Until(Instant::now() + dur)) {
+            Some(CommMsg::Message(t)) => Ok(t),
+            Some(CommMsg::Error(e)) => Err(RecvTimeoutError::Errored(e)),
+            Some(CommMsg::Close) => Err(RecvTimeoutError::Disconnected),
+            Some(CommMsg::Barrier(_)) | Some(CommMsg::CloseWith(_)) => unreachable!(),
+            None if self.is_closed() => Err(RecvTimeoutError::Disconnected),
+            None => Err(RecvTimeoutError::Timeout)
+        }
+    }
+
+    /// Returns the next message in the channel.  This method will block
+    /// until either a message arrives, the channel is closed, or `deadline`
+    /// is reached.
+    ///
+    /// This behaves exactly like `recv_timeout`.  A deadline that has
+    /// already passed checks the queue once without blocking.
+    pub fn recv_deadline(&self, deadline: Instant) -> Option<T> {
+        self.next_msg(Wait::Until(deadline), true).and_then(|m| self.unwrap_msg(m))
+    }
+
+    /// Returns a reference to the next message without removing it from the
+    /// channel.  The next call to any of the receiving methods will return
+    /// this message.
+    ///
+    /// This does not block.  If there is no message ready, or the channel
+    /// is closed, `None` is returned.  An error is handled the same way as
+    /// `recv`, so it can be retrieved with `take_error`.
+    ///
+    /// This takes `&mut self` so that the returned reference can't be
+    /// invalidated by another call that removes the message.
+    pub fn peek(&mut self) -> Option<&T> {
+        if self.front.get_mut().unwrap().is_empty() {
+            if let Some(m) = self.recv() {
+                self.front.get_mut().unwrap().push_back(m);
+            }
+        }
+        self.front.get_mut().unwrap().front()
+    }
+
+    /// Puts a message back at the front of the channel, so that the next
+    /// call to any of the receiving methods returns it before anything
+    /// else.
+    ///
+    /// This is meant for retrying a message that couldn't be handled.
+    /// Several messages can be put back, and the last one put back is
+    /// received first.  They are kept in the Receiver itself, so they don't
+    /// count towards the bound of a bounded channel, and they are received
+    /// even if the channel has been closed since.
+    pub fn unrecv(&self, t: T) {
+        self.front.lock().unwrap().push_front(t);
+        self.shared.wake();
+    }
+
+    /// Returns the next message in the channel like `recv`, wrapped in a
+    /// guard that puts it back with `unrecv` if it is dropped without
+    /// calling `RecvGuard::ack`.
+    ///
+    /// This makes it possible to retry a message whose handling failed,
+    /// e.g. by panicking.  A message that is put back is returned by the
+    /// next call to any of the receiving methods, from any thread.  If
+    /// several guards are dropped, the last message put back is received
+    /// first.  Messages that are put back only live in this Receiver, so
+    /// they are lost along with it.
+    pub fn recv_guarded(&self) -> Option<RecvGuard<'_, T, E>> {
+        self.recv().map(|m| RecvGuard {
+            receiver: self,
+            msg: Some(m)
+        })
+    }
+
+    /// Returns every message that is currently in the channels queue
+    /// without blocking.
+    ///
+    /// Draining stops when the queue is empty or the channel is closed.  If
+    /// the channel was closed with an error, the messages that came before
+    /// it are still returned and the error can be retrieved with
+    /// `take_error`.
+    pub fn drain(&self) -> Vec<T> {
+        let mut out = Vec::new();
+        while let Some(m) = self.recv() {
+            out.push(m);
+        }
+        out
+    }
+
+    /// Moves every message that is currently in the channels queue into
+    /// `c` without blocking.
+    ///
+    /// This stops like `drain`, but adds the messages to an existing
+    /// collection instead of a new `Vec`.  If the channel was closed with
+    /// an error, it can be retrieved with `take_error`.
+    pub fn drain_into<C: Extend<T>>(&self, c: &mut C) {
+        c.extend(self.iter());
+    }
+
+    /// Moves every message into `c`, blocking until the channel is closed.
+    ///
+    /// If the channel was closed with an error, it can be retrieved with
+    /// `take_error`.
+    pub fn drain_block_into<C: Extend<T>>(&self, c: &mut C) {
+        c.extend(self.blocking_iter());
+    }
+
+    /// Appends up to `max` of the messages that are currently in the
+    /// channels queue to `buf` without blocking, and returns how many were
+    /// appended.
+    ///
+    /// This stops early when the queue is empty or the channel is closed,
+    /// like `drain`.  If the channel was closed with an error, the messages
+    /// that came before it are still appended and the error can be
+    /// retrieved with `take_error`.
+    pub fn recv_many(&self, buf: &mut Vec<T>, max: usize) -> usize {
+        let start = buf.len();
+        while buf.len() - start < max {
+            match self.recv() {
+                Some(m) => buf.push(m),
+                None => break
+            }
+        }
+        buf.len() - start
+    }
+
+    /// Returns the next `n` messages in the channel, blocking for each of
+    /// them like `recv_block`.
+    ///
+    /// Fewer than `n` messages are returned if the channel is closed first.
+    /// If it was closed with an error, the error can be retrieved with
+    /// `take_error`.
+    pub fn take_block(&self, n: usize) -> Vec<T> {
+        let mut out = Vec::new();
+        while out.len() < n {
+            match self.recv_block() {
+                Some(m) => out.push(m),
+                None => break
+            }
+        }
+        out
+    }
+
+    /// Returns every message that is currently in the channels queue
+    /// without blocking, along with the reason it stopped.
+    ///
+    /// The second element is `None` if the queue ran out of messages while
+    /// the channel is still open.  Otherwise it says whether the channel
+    /// was closed regularly or by an error, in which case the error is
+    /// moved out of the receiver.
+    pub fn recv_all(&self) -> (Vec<T>, Option<Terminal<E>>) {
+        let mut out = Vec::new();
+        loop {
+            match self.try_recv() {
+                RecvStatus::Message(m) => out.push(m),
+                RecvStatus::Empty => return (out, None),
+                RecvStatus::Closed => return (out, Some(Terminal::Closed)),
+                RecvStatus::Errored(e) => return (out, Some(Terminal::Errored(e)))
+            }
+        }
+    }
+
+    /// Blocks until the channel is closed and returns every message that
+    /// was received, or the error if the channel was closed with one.
+    ///
+    /// The messages received before an error are discarded.
+    pub fn collect_results(self) -> Result<Vec<T>, E> {
+        let mut out = Vec::new();
+        while let Some(m) = self.recv_checked_block()? {
+            out.push(m);
+        }
+        Ok(out)
+    }
+
+    /// Blocks until the channel is closed and returns every message that
+    /// was received.
+    ///
+    /// If the channel was closed with an error, the messages are returned
+    /// along with the error in `Err`, unlike `collect_results`.
+    pub fn into_vec_block(self) -> Result<Vec<T>, (Vec<T>, E)> {
+        let out: Vec<T> = self.blocking_iter().collect();
+        match self.take_error() {
+            Some(e) => Err((out, e)),
+            None => Ok(out)
+        }
+    }
+
+    /// Blocks until the channel is closed, folding every message that
+    /// arrives into an accumulator with `f`.
+    ///
+    /// Returns the accumulator along with the error the channel was closed
+    /// with, if any.
+    pub fn fold_block<B, F>(self, init: B, f: F) -> (B, Option<E>)
+    where F: FnMut(B, T) -> B {
+        let acc = self.blocking_iter().fold(init, f);
+        (acc, self.take_error())
+    }
+
+    /// Blocks until the channel is closed, discarding any messages that
+    /// arrive in the meantime.
+    ///
+    /// If the channel was closed with an error, the error can still be
+    /// retrieved with `take_error`.
+    pub fn wait_closed(&self) {
+        while self.recv_block().is_some() {}
+    }
+
+    /// Blocks until the channel is closed or `dur` has elapsed, discarding
+    /// any messages that arrive in the meantime.
+    ///
+    /// Returns true if the channel was closed.
+    pub fn wait_closed_timeout(&self, dur: Duration) -> bool {
+        let deadline = Instant::now() + dur;
+        while self.recv_deadline(deadline).is_some() {}
+        self.is_closed()
+    }
+
+    /// Calls `f` once the channel is closed, with the error if it was
+    /// closed by one.
+    ///
+    /// This takes ownership of the receiver and spawns a thread that
+    /// blocks until the channel is closed, discarding any messages that
+    /// arrive in the meantime, and then calls `f` on that thread.
+    pub fn on_close<F: FnOnce(Option<E>) + Send + 'static>(self, f: F) {
+        thread::spawn(move || {
+            self.wait_closed();
+            f(self.take_error())
+        });
+    }
+
+    /// Returns the next raw message asynchronously.
+    ///
+    /// Unlike `recv`, an error is handed back inline as `CommMsg::Error`
+    /// instead of being stored for `take_error`.  Messages queued behind an
+    /// error are still served first, and the error is returned once the
+    /// queue is empty (see `set_drain_on_error`).  The channel is marked as
+    /// closed after an error or a `CommMsg::Close`.
+    ///
+    /// * If there is a message in the channels queue, it is returned in `Some`.
+    /// * If there is no message ready, None is returned.
+    /// * If the channel is closed, None is returned.
+    pub fn recv_msg(&self) -> Option<CommMsg<T, E>> {
+        self.pull(Wait::Never)
+    }
+
+    /// Returns the next raw message in the channel, blocking until one
+    /// arrives or the channel is closed.
+    ///
+    /// Errors are handed back inline in the same way as `recv_msg`.
+    pub fn recv_msg_block(&self) -> Option<CommMsg<T, E>> {
+        self.pull(Wait::Forever)
+    }
+
+    /// Pulls the next raw message, waiting for one as long as `wait`
+    /// allows.
+    fn pull(&self, wait: Wait) -> Option<CommMsg<T, E>> {
+        self.next_msg(wait, false)
+    }
+
+    /// Pulls the next message like `pull`, storing errors so that they can
+    /// be retrieved with `take_error`, the way `recv` does.
+    ///
+    /// An error is stored before the inner channel is unlocked, so that
+    /// `status` never sees the channel closed by an error that isn't there
+    /// yet.  When errors are collected, the messages after them are pulled
+    /// in the same go.
+    fn next_msg(&self, wait: Wait, store: bool) -> Option<CommMsg<T, E>> {
+        let deadline = match wait {
+            Wait::Never => {
+                // The lock is only ever held briefly, since waiting receives
+                // park outside of it, so this doesn't block for long.
+                let inner = self.inner.lock().unwrap();
+                return self.step(&inner, store, Duration::from_secs(0)).unwrap_or(None)
+            }
+            Wait::Forever => None,
+            Wait::Until(deadline) => Some(deadline)
+        };
+
+        // The inner channel is only locked while it is checked, and we wait
+        // for a change outside of it, so that other threads can receive or
+        // look at the state in the meantime.  A channel that can't wake us
+        // is waited on inside the inner channel instead, a slice at a time.
+        let _waiting = self.shared.start_waiting();
+        loop {
+            let seen = self.shared.notify.epoch();
+            let polled = self.shared.polled();
+            let patience = match deadline {
+                _ if !polled => Duration::from_secs(0),
+                None => POLL_INTERVAL,
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).min(POLL_INTERVAL)
+            };
+            if let Some(m) = self.step(&self.inner.lock().unwrap(), store, patience) {
+                return m
+            }
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => return None,
+                _ if polled => {}
+                _ => self.shared.notify.wait(seen, deadline)
+            }
+        }
+    }
+
+    /// Takes the next message out of the locked inner channel like
+    /// `take_msg`, storing errors if `store` is set.
+    ///
+    /// Returns None if there was nothing to take yet, and `Some` with what
+    /// the receive should return otherwise.
+    fn step(&self, inner: &mpsc::Receiver<CommMsg<T, E>>, store: bool, patience: Duration)
+    -> Option<Option<CommMsg<T, E>>> {
+        let mut patience = patience;
+        loop {
+            match self.take_msg(inner, patience)? {
+                Some(CommMsg::Error(e)) if store => {
+                    self.store_error(e);
+                    if !self.collect_errors.load(Ordering::SeqCst) {
+                        return Some(None)
+                    }
+                    patience = Duration::from_secs(0);
+                }
+                m => return Some(m)
+            }
+        }
+    }
+
+    /// Takes the next message out of the locked inner channel, waiting up
+    /// to `patience` for one, and updates the state of the receiver for it.
+    ///
+    /// Unless errors are being collected, an error is held back until the
+    /// messages queued behind it have been served.  Once the queue runs dry
+    /// the channel is closed and the error is handed out.  With
+    /// `set_drain_on_error(false)` it is handed out straight away instead.
+    fn take_msg(&self, inner: &mpsc::Receiver<CommMsg<T, E>>, patience: Duration)
+    -> Option<Option<CommMsg<T, E>>> {
+        if let Some(m) = self.front.lock().unwrap().pop_front() {
+            return Some(Some(CommMsg::Message(m)))
+        }
+        if self.is_closed() {
+            return Some(None)
+        }
+        loop {
+            let draining = self.pending_error.lock().unwrap().is_some();
+            let next = if draining || patience == Duration::from_secs(0) {
+                inner.try_recv().map_err(timeout_error)
+            } else {
+                inner.recv_timeout(patience)
+            };
+            match next {
+                Ok(CommMsg::Message(m)) => {
+                    self.consumed();
+                    return Some(Some(CommMsg::Message(m)))
+                }
+                Ok(CommMsg::Error(e)) => {
+                    self.consumed();
+                    if self.collect_errors.load(Ordering::SeqCst) {
+                        return Some(Some(CommMsg::Error(e)))
+                    }
+                    if !draining && !self.drain_on_error.load(Ordering::SeqCst) {
+                        self.closed_by_error.store(true, Ordering::SeqCst);
+                        self.mark_closed();
+                        return Some(Some(CommMsg::Error(e)))
+                    }
+                    if !draining {
+                        *self.pending_error.lock().unwrap() = Some(e);
+                        self.closed_by_error.store(true, Ordering::SeqCst);
+                    }
+                }
+                Ok(CommMsg::Barrier(signal)) => signal.fire(),
+                Ok(CommMsg::CloseWith(v)) => {
+                    *self.close_value.lock().unwrap() = Some(v);
+                    self.ended.store(true, Ordering::SeqCst);
+                    self.mark_closed();
+                    return Some(Some(self.pending_error.lock().unwrap().take()
+                                     .map_or(CommMsg::Close, CommMsg::Error)))
+                }
+                Ok(CommMsg::Close) => {
+                    self.ended.store(true, Ordering::SeqCst);
+                    self.mark_closed();
+                    return Some(Some(self.pending_error.lock().unwrap().take()
+                                     .map_or(CommMsg::Close, CommMsg::Error)))
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) if !draining => return None,
+                Err(e) => {
+                    if e == mpsc::RecvTimeoutError::Disconnected {
+                        self.ended.store(true, Ordering::SeqCst);
+                    }
+                    self.mark_closed();
+                    return Some(self.pending_error.lock().unwrap().take().map(CommMsg::Error))
+                }
+            }
+        }
+    }
+
+    /// Marks the channel as closed, waking the other threads that wait for
+    /// it so that they see it too.
+    fn mark_closed(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.shared.wake();
+    }
+
+    /// Returns the message if there was one, storing the error so that it
+    /// can be retrieved with `take_error`.
+    fn unwrap_msg(&self, msg: CommMsg<T, E>) -> Option<T> {
+        match msg {
+            CommMsg::Message(m) => Some(m),
+            CommMsg::Error(e) => {
+                self.store_error(e);
+                None
+            }
+            CommMsg::Barrier(_) | CommMsg::CloseWith(_) => unreachable!(),
+            CommMsg::Close => None
+        }
+    }
+
+    fn store_error(&self, e: E) {
+        self.errors.write().unwrap().push(e);
+        self.errored.store(true, Ordering::SeqCst);
+    }
+
+    /// Decrements the queued message count.  Messages sent by a Sender that
+    /// doesn't share our counter (see `from_old`) never push it below zero.
+    fn consumed(&self) {
+        let _ = self.shared.len.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        self.shared.release_slot();
+    }
+
+    /// Returns the number of messages (including an error) that have been
+    /// sent but not yet received.
+    ///
+    /// This count is only tracked for channels created by `channel` or
+    /// `sync_channel`; a receiver built with `from_old` always reports 0.
+    /// On a channel with a bound of 0, a sender waiting for its message to
+    /// be taken is already counted.  On other bounded channels, a sender
+    /// blocked waiting for room isn't counted until it gets some.
+    pub fn len(&self) -> usize {
+        self.shared.len.load(Ordering::SeqCst) + self.front.lock().unwrap().len()
+    }
+
+    /// Returns whether the channel is closed, whether it has an error, and
+    /// how many messages are queued, all at once.
+    ///
+    /// The snapshot is taken under the lock that receives hold while they
+    /// take a message and close the channel, so a receive on another
+    /// thread is either entirely in it or not at all, and the three values
+    /// are consistent with each other.  Only `len` can grow concurrently,
+    /// as senders keep sending.  This waits for a receive that is taking a
+    /// message, but not for one that is blocked waiting for one, except
+    /// for up to a millisecond on a channel with a bound of 0.
+    pub fn status(&self) -> ReceiverStatus {
+        let _state = self.inner.lock().unwrap();
+        ReceiverStatus {
+            closed: self.is_closed(),
+            errored: self.has_error(),
+            len: self.len()
+        }
+    }
+
+    /// Asks the senders of this channel to stop sending.
+    ///
+    /// This only sets a flag that senders can check with
+    /// `Sender::is_shutdown_requested`; it's up to them to stop.  The
+    /// channel stays open, and messages that are still sent are received
+    /// as usual.
+    pub fn request_close(&self) {
+        self.shared.close_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the number of Senders for this channel that are still alive.
+    ///
+    /// This reaches 0 once the last Sender has been dropped (including by
+    /// `close` or `error`), which is when the channel disconnects.  The
+    /// count is only tracked for channels created by `channel` or
+    /// `sync_channel`; a receiver built with `from_old` always reports 0.
+    /// A sender taken apart with `Sender::into_inner` no longer counts.
+    pub fn sender_count(&self) -> usize {
+        self.shared.senders.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if there are no messages waiting to be received.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a receiver that can be cloned so that several consumers can
+    /// take messages from the same channel.
+    pub fn into_shared(self) -> SharedReceiver<T, E> {
+        SharedReceiver { inner: Arc::new(Mutex::new(self)) }
+    }
+
+    /// Returns a receiver that converts the error of this channel with `f`.
+    ///
+    /// `f` is called at most once, when the error is first seen by one of
+    /// the receiving methods or by `take_error`.  If several errors are
+    /// collected (see `set_collect_errors`), only the first is converted;
+    /// the rest stay on the wrapped receiver.
+    pub fn map_err<E2, F>(self, f: F) -> MapErrReceiver<T, E, E2, F>
+    where F: FnOnce(E) -> E2 {
+        MapErrReceiver {
+            receiver: self,
+            f: Cell::new(Some(f)),
+            error: RefCell::new(None)
+        }
+    }
+
+    /// Returns a receiver that gets the result of calling `f` on every
+    /// message of this one.
+    ///
+    /// This spawns a thread that receives from this receiver and calls `f`,
+    /// so `f` runs on that thread rather than the consumer's.  Errors are
+    /// forwarded unchanged, and closing this channel closes the new one.
+    pub fn map<U, F>(self, mut f: F) -> Receiver<U, E>
+    where U: Send + 'static, F: FnMut(T) -> U + Send + 'static {
+        self.adapt(move |t| Some(f(t)))
+    }
+
+    /// Returns a receiver that only gets the messages of this one for
+    /// which `pred` returns true.
+    ///
+    /// Like `map`, this spawns a thread that `pred` runs on.  Errors are
+    /// forwarded unchanged, and closing this channel closes the new one.
+    pub fn filter<F>(self, mut pred: F) -> Receiver<T, E>
+    where F: FnMut(&T) -> bool + Send + 'static {
+        self.adapt(move |t| if pred(&t) { Some(t) } else { None })
+    }
+
+    /// Spawns a thread forwarding every message for which `f` returns
+    /// `Some` to a new channel, along with errors and the close marker.
+    fn adapt<U, F>(self, mut f: F) -> Receiver<U, E>
+    where U: Send + 'static, F: FnMut(T) -> Option<U> + Send + 'static {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            while let Some(m) = self.recv_msg_block() {
+                let sent = match m {
+                    CommMsg::Message(t) => match f(t) {
+                        Some(u) => tx.send_msg(CommMsg::Message(u)),
+                        None => Ok(())
+                    },
+                    CommMsg::Error(e) => tx.send_msg(CommMsg::Error(e)),
+                    CommMsg::Barrier(_) | CommMsg::CloseWith(_) => unreachable!(),
+                    CommMsg::Close => match self.take_close_value() {
+                        Some(v) => tx.send_msg(CommMsg::CloseWith(v)),
+                        None => tx.send_msg(CommMsg::Close)
+                    }
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Returns true if the channel was closed with an error.
+    ///
+    /// This stays true while the error is shared with `error_arc`, so it
+    /// agrees with `close_reason`.
+    pub fn has_error(&self) -> bool {
+        self.errored.load(Ordering::SeqCst) || self.shared_error.lock().unwrap().is_some()
+    }
+
+    /// Returns true if an error has already been received, or if an error
+    /// is next in the channels queue.  This does not block.
+    ///
+    /// Unlike `has_error`, this checks the queue, but it never skips over
+    /// messages to do so: an error queued behind a message is not reported
+    /// until that message has been received.  A message that is checked
+    /// is held back the same way as by `peek` and is returned by the next
+    /// call to any of the receiving methods.
+    pub fn poll_error(&self) -> bool {
+        if self.front.lock().unwrap().is_empty() {
+            if let Some(m) = self.recv() {
+                self.front.lock().unwrap().push_back(m);
+                self.shared.wake();
+            }
+        }
+        self.has_error() || self.pending_error.lock().unwrap().is_some()
+    }
+
+    /// Returns the error if the channel was closed with an error.
+    /// This method moves the error out of the Receiver, so subsequent
+    /// calls will return None.
+    ///
+    /// Returns `None` if the channel wasn't closed with an error, or if
+    /// the error has already been taken.
+    ///
+    /// If several errors have been collected (see `set_collect_errors`),
+    /// the oldest one is returned.
+    pub fn take_error(&self) -> Option<E> {
+        let mut errors = self.errors.write().unwrap();
+        let e = if errors.is_empty() { None } else { Some(errors.remove(0)) };
+        self.errored.store(!errors.is_empty(), Ordering::SeqCst);
+        e
+    }
+
+    /// Stores `e` as if a Sender had sent it, so that errors found by the
+    /// consumer (e.g. a message that fails to decode) can be handled the
+    /// same way as the ones sent by the producer.
+    ///
+    /// Unless errors are being collected, the channel is closed right away
+    /// with `e`, and messages still in the queue are no longer received.
+    /// `take_error` returns `e`, and `clear_error` reopens the channel like
+    /// it would after a sent error.
+    pub fn inject_error(&self, e: E) {
+        let _state = self.inner.lock().unwrap();
+        self.store_error(e);
+        if !self.collect_errors.load(Ordering::SeqCst) {
+            self.closed_by_error.store(true, Ordering::SeqCst);
+            self.mark_closed();
+        }
+    }
+
+    /// Lends the error the channel was closed with to `f`, without moving
+    /// it out of the Receiver.
+    ///
+    /// `f` gets the same error `take_error` would return, or `None`.  The
+    /// errors are locked for reading while `f` runs, so `f` must not call
+    /// `take_error` or anything else that changes them.
+    pub fn with_error<R, F: FnOnce(Option<&E>) -> R>(&self, f: F) -> R {
+        let errors = self.errors.read().unwrap();
+        f(errors.first())
+    }
+
+    /// Returns the error if the channel was closed with an error, in an
+    /// `Arc` that can be shared between threads.
+    ///
+    /// The first call moves the error out of the Receiver like
+    /// `take_error`, and every call after that returns a clone of the same
+    /// `Arc`, so any number of threads can hold on to the error without
+    /// `E` being `Clone`.  In exchange the error can't be moved out by
+    /// value anymore, except with `Arc::try_unwrap` once every other clone
+    /// is gone, and `take_error` and `with_error` no longer see it.
+    /// `has_error` keeps returning true until `clear_error` lets go of it.
+    pub fn error_arc(&self) -> Option<Arc<E>>
+    where E: Sync {
+        let mut slot = self.shared_error.lock().unwrap();
+        if let Some(e) = slot.as_ref().and_then(|e| e.downcast_ref::<Arc<E>>()) {
+            return Some(e.clone())
+        }
+        let e = Arc::new(self.take_error()?);
+        *slot = Some(Box::new(e.clone()));
+        Some(e)
+    }
+
+    /// Returns a clone of the error if the channel was closed with an
+    /// error, leaving the error in the Receiver.
+    ///
+    /// This returns the same error that `take_error` would.
+    pub fn clone_error(&self) -> Option<E>
+    where E: Clone {
+        self.with_error(|e| e.cloned())
+    }
+
+    /// Returns every error that has been received, in the order they
+    /// arrived, and removes them from the Receiver.
+    ///
+    /// Unless `set_collect_errors` is enabled, there is at most one.
+    pub fn take_errors(&self) -> Vec<E> {
+        self.errored.store(false, Ordering::SeqCst);
+        let mut errors = self.errors.write().unwrap();
+        errors.drain(..).collect()
+    }
+
+    /// Takes the error out of the Receiver and reopens the channel if the
+    /// error was what closed it, so that later calls to `recv` carry on
+    /// with the messages sent after the error.
+    ///
+    /// This is only useful when the senders are still alive and keep
+    /// sending after an error, for protocols where errors aren't fatal.
+    /// If every sender is gone, or the channel was closed regularly, it
+    /// stays closed.  If several errors were collected, the oldest one is
+    /// returned and the rest are discarded.  An error shared with
+    /// `error_arc` is let go of, but can't be returned.
+    pub fn clear_error(&self) -> Option<E> {
+        let _state = self.inner.lock().unwrap();
+        self.shared_error.lock().unwrap().take();
+        let mut errors = self.take_errors().into_iter();
+        let e = errors.next().or_else(|| self.pending_error.lock().unwrap().take());
+        self.pending_error.lock().unwrap().take();
+        if self.closed_by_error.load(Ordering::SeqCst) {
+            self.closed_by_error.store(false, Ordering::SeqCst);
+            self.closed.store(self.ended.load(Ordering::SeqCst), Ordering::SeqCst);
+        }
+        e
+    }
+
+    /// Sets whether errors are collected instead of closing the channel.
+    ///
+    /// When enabled, `recv`, `recv_block` and `recv_timeout` store every
+    /// error they encounter and carry on with the messages after it, so a
+    /// channel only ends when it is closed.  The stored errors can be
+    /// retrieved with `take_errors`, or one at a time with `take_error`.
+    /// The raw `recv_msg` methods and `try_recv` still hand each error back
+    /// inline, but don't close the channel.
+    ///
+    /// This is disabled by default.
+    pub fn set_collect_errors(&self, collect: bool) {
+        self.collect_errors.store(collect, Ordering::SeqCst);
+    }
+
+    /// Sets whether the messages queued behind an error are still received.
+    ///
+    /// When enabled, an error is held back until every message that was
+    /// already queued behind it (e.g. sent by a clone of the Sender that
+    /// errored) has been received, and then closes the channel.  When
+    /// disabled, the error closes the channel as soon as it is received,
+    /// and the messages behind it are never returned.  Collecting errors
+    /// with `set_collect_errors` takes precedence over this setting.
+    ///
+    /// This is enabled by default.
+    pub fn set_drain_on_error(&self, drain: bool) {
+        self.drain_on_error.store(drain, Ordering::SeqCst);
+    }
+
+    /// Returns the value that the channel was closed with by
+    /// `Sender::close_with`.  This method moves the value out, so
+    /// subsequent calls will return None.
+    pub fn take_close_value(&self) -> Option<E> {
+        self.close_value.lock().unwrap().take()
+    }
+
+    /// Returns how the channel was closed, or `None` if it is still open.
+    ///
+    /// An error close is still reported as `CloseReason::Error` after the
+    /// error has been taken out of the receiver.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        if !self.is_closed() {
+            None
+        } else if self.closed_by_error.load(Ordering::SeqCst) {
+            Some(CloseReason::Error)
+        } else {
+            Some(CloseReason::Clean)
+        }
+    }
+
+    /// Returns true if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Returns an iterator over the messages in this receiver.
+    /// The iterator is non-blocking, and borrows this receiver.
+    ///
+    /// Note that `std::sync::mpsc::Receiver::iter` blocks.  A future major
+    /// version may make this method blocking to match; use `try_iter` or
+    /// `blocking_iter` to be explicit.
+    pub fn iter(&self) -> ReceiverIterator<'_, T, E> {
+        ReceiverIterator {
+            blocking: false,
+            done: false,
+            reference: MaybeOwned::Borrowed(self)
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver.
+    /// The iterator is non-blocking, and borrows this receiver.
+    ///
+    /// This is the same as `iter`, and is named after
+    /// `std::sync::mpsc::Receiver::try_iter`.
+    pub fn try_iter(&self) -> ReceiverIterator<'_, T, E> {
+        self.iter()
+    }
+
+    /// Returns an iterator over the messages in this receiver.
+    /// The iterator is blocking and borrows this receiver.
+    pub fn blocking_iter(&self) -> ReceiverIterator<'_, T, E> {
+        ReceiverIterator {
+            blocking: true,
+            done: false,
+            reference: MaybeOwned::Borrowed(self)
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver that stops
+    /// at the first message equal to `poison`.  The iterator blocks like
+    /// `blocking_iter`, and borrows this receiver.
+    ///
+    /// Unlike `iter_until`, the poison message is consumed: the next call
+    /// to any of the receiving methods returns the message after it.  Once
+    /// stopped, the iterator keeps returning `None`.
+    pub fn iter_until_value(&self, poison: T) -> UntilValueIterator<'_, T, E>
+    where T: PartialEq {
+        UntilValueIterator {
+            receiver: self,
+            poison,
+            done: false
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver that skips
+    /// runs of equal consecutive messages.  The iterator is non-blocking,
+    /// and borrows this receiver.
+    ///
+    /// A message is dropped if it equals the last message the iterator
+    /// yielded, so `1, 1, 2, 2, 1` yields `1, 2, 1`.  The iterator keeps a
+    /// clone of the last message to compare against, which lives as long
+    /// as the iterator does.
+    pub fn iter_dedup(&self) -> DedupIterator<'_, T, E>
+    where T: PartialEq + Clone {
+        DedupIterator {
+            inner: self.iter(),
+            last: None
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver that stops
+    /// before the first message for which `stop` returns true.  The
+    /// iterator blocks like `blocking_iter`, and borrows this receiver.
+    ///
+    /// The message that stopped the iterator is put back with `unrecv`, so
+    /// the next call to any of the receiving methods returns it, followed
+    /// by the messages after it.  Messages that were already put back are
+    /// checked against `stop` like any other.  Once stopped, the iterator
+    /// keeps returning `None`.
+    pub fn iter_until<F>(&self, stop: F) -> UntilIterator<'_, T, E, F>
+    where F: FnMut(&T) -> bool {
+        UntilIterator {
+            receiver: self,
+            stop,
+            done: false
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver.  The
+    /// iterator blocks for each message, but stops when none arrives
+    /// within `idle`.  It borrows this receiver.
+    pub fn timeout_iter(&self, idle: Duration) -> TimeoutIterator<'_, T, E> {
+        TimeoutIterator {
+            receiver: self,
+            idle,
+            stopped: None
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver.
+    /// The iterator is non-blocking and consumes this receiver.
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter(self) -> ReceiverIterator<'static, T, E> {
+        ReceiverIterator {
+            blocking: false,
+            done: false,
+            reference: MaybeOwned::Owned(self)
+        }
+    }
+
+    /// Returns an iterator over the messages in this receiver that yields
+    /// an error that closes the channel as a final `Err`.
+    /// The iterator blocks if `blocking` is true, and borrows this receiver.
+    pub fn iter_results(&self, blocking: bool) -> ResultIterator<'_, T, E> {
+        ResultIterator {
+            blocking,
+            reference: MaybeOwned::Borrowed(self)
+        }
+    }
+
+    /// Polls for the next message, registering the current task to be woken
+    /// when one might have arrived.
+    ///
+    /// * If there is a message in the channels queue, it is returned in
+    ///   `Poll::Ready(Some)`.
+    /// * If there is no message ready, `Poll::Pending` is returned.
+    /// * If the channel is closed, `Poll::Ready(None)` is returned.
+    /// * If the channel is closed with an error, `Poll::Ready(None)` is
+    ///   returned and the error can be retrieved with `take_error`.
+    ///
+    /// Rather than spawning a thread like `into_stream`, every Sender of
+    /// the channel wakes the registered task after each send, and the last
+    /// Sender wakes it when it goes away.  Only the most recently polling
+    /// task is woken.
+    ///
+    /// On a channel with a bound of 0, a Sender only finishes sending, and
+    /// wakes the task, once the message has been taken, and a bare
+    /// `std::sync::mpsc` sender (see `from_old` and `Sender::into_inner`)
+    /// doesn't know about the Receiver at all.  On these channels the task
+    /// is woken straight away whenever this returns `Poll::Pending`, so it
+    /// keeps polling until a message arrives, at the cost of keeping the
+    /// executor busy.
+    #[cfg(feature = "futures")]
+    pub fn poll_recv(&self, cx: &mut Context) -> Poll<Option<T>> {
+        if let Some(m) = self.recv() {
+            return Poll::Ready(Some(m))
+        }
+        if self.is_closed() {
+            return Poll::Ready(None)
+        }
+
+        // Register before checking again so that a message sent in
+        // between isn't missed.
+        *self.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+        match self.recv() {
+            Some(m) => Poll::Ready(Some(m)),
+            None if self.is_closed() => Poll::Ready(None),
+            None => {
+                if self.shared.polled() {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Converts this receiver into a `futures::Stream` of its messages.
+    ///
+    /// See `BchannelStream` for how the stream is driven.
+    #[cfg(feature = "futures")]
+    pub fn into_stream(self) -> BchannelStream<T, E> {
+        BchannelStream::new(self)
+    }
+
+    /// Returns an iterator over the messages in this receiver.
+    /// The iterator is blocking, and consumes this receiver.
+    pub fn into_blocking_iter(self) -> ReceiverIterator<'static, T, E> {
+        ReceiverIterator {
+            blocking: true,
+            done: false,
+            reference: MaybeOwned::Owned(self)
+        }
+    }
+
+    /// Converts this receiver into a reader over the bytes of the chunks it
+    /// receives.
+    ///
+    /// `read` blocks until a chunk arrives, and a chunk that doesn't fit
+    /// into the buffer is carried over to the next `read`.  The reader
+    /// returns end of file once the channel is closed, or the error
+    /// converted into an `io::Error` if the channel was closed with one.
+    pub fn into_read(self) -> ChannelRead<T, E>
+    where T: AsRef<[u8]>, E: Into<io::Error> {
+        ChannelRead {
+            receiver: self,
+            chunk: None
+        }
+    }
+
+    /// Moves this receiver into a new thread and calls `f` there with a
+    /// blocking iterator over its messages.
+    ///
+    /// Returns the handle of the thread, which can be joined to get the
+    /// result of `f`.
+    pub fn spawn_consuming<F, R>(self, f: F) -> thread::JoinHandle<R>
+    where F: FnOnce(ReceiverIterator<'static, T, E>) -> R + Send + 'static,
+          R: Send + 'static {
+        thread::spawn(move || f(self.into_blocking_iter()))
+    }
+
+    /// Returns an iterator over the messages in this receiver that yields
+    /// an error that closes the channel as a final `Err`.
+    /// The iterator is blocking, and consumes this receiver.
+    pub fn into_result_iter(self) -> ResultIterator<'static, T, E> {
+        ResultIterator {
+            blocking: true,
+            reference: MaybeOwned::Owned(self)
+        }
+    }
+}
+
+impl <T, E, E2, F> MapErrReceiver<T, E, E2, F>
+where T: Send + 'static, E: Send + 'static, F: FnOnce(E) -> E2 {
+    /// Returns a reference to the wrapped receiver.
+    pub fn get_ref(&self) -> &Receiver<T, E> {
+        &self.receiver
+    }
+
+    /// Returns the wrapped receiver, discarding any converted error.
+    pub fn into_inner(self) -> Receiver<T, E> {
+        self.receiver
+    }
+
+    /// Converts the error of the wrapped receiver the first time that it
+    /// is seen.
+    fn map_error(&self) {
+        if !self.receiver.has_error() {
+            return
+        }
+        if let Some(f) = self.f.take() {
+            *self.error.borrow_mut() = self.receiver.take_error().map(f);
+        }
+    }
+
+    /// Returns the next message asynchronously.  See `Receiver::recv`.
+    pub fn recv(&self) -> Option<T> {
+        let m = self.receiver.recv();
+        self.map_error();
+        m
+    }
+
+    /// Returns the next message in the channel, blocking until one arrives
+    /// or the channel is closed.  See `Receiver::recv_block`.
+    pub fn recv_block(&self) -> Option<T> {
+        let m = self.receiver.recv_block();
+        self.map_error();
+        m
+    }
+
+    /// Returns the next message in the channel, blocking until one arrives,
+    /// the channel is closed, or `dur` has elapsed.  See
+    /// `Receiver::recv_timeout`.
+    pub fn recv_timeout(&self, dur: Duration) -> Option<T> {
+        let m = self.receiver.recv_timeout(dur);
+        self.map_error();
+        m
+    }
+
+    /// Returns true if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.is_closed()
+    }
+
+    /// Returns true if the channel was closed with an error.
+    pub fn has_error(&self) -> bool {
+        self.error.borrow().is_some() || self.receiver.has_error()
+    }
+
+    /// Returns the converted error if the channel was closed with an error.
+    /// This method moves the error out, so subsequent calls will return
+    /// None.
+    pub fn take_error(&self) -> Option<E2> {
+        self.map_error();
+        self.error.borrow_mut().take()
+    }
+}
+
+impl <T, E> SharedReceiver<T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Returns the next message asynchronously.  This behaves like
+    /// `Receiver::recv`.
+    pub fn recv(&self) -> Option<T> {
+        self.inner.lock().unwrap().recv()
+    }
+
+    /// Returns the next message in the channel, blocking until one arrives
+    /// or the channel is closed.  This behaves like `Receiver::recv_block`.
+    ///
+    /// The other clones can't receive while one of them is blocked here.
+    pub fn recv_block(&self) -> Option<T> {
+        self.inner.lock().unwrap().recv_block()
+    }
+
+    /// Returns true if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().unwrap().is_closed()
+    }
+
+    /// Returns true if the channel was closed with an error.
+    pub fn has_error(&self) -> bool {
+        self.inner.lock().unwrap().has_error()
+    }
+
+    /// Returns the error if the channel was closed with an error.  The
+    /// error is moved out, so only one of the clones will get it.
+    pub fn take_error(&self) -> Option<E> {
+        self.inner.lock().unwrap().take_error()
+    }
+}
+
+impl <T: Send, E: Send> Clone for SharedReceiver<T, E> {
+    fn clone(&self) -> SharedReceiver<T, E> {
+        SharedReceiver { inner: self.inner.clone() }
+    }
+}
+
+impl <T, E> From<mpsc::Receiver<CommMsg<T, E>>> for Receiver<T, E>
+where T: Send + 'static, E: Send + 'static {
+    fn from(v: mpsc::Receiver<CommMsg<T, E>>) -> Receiver<T, E> {
+        let shared = Shared::new();
+        shared.untracked.store(true, Ordering::SeqCst);
+        Receiver::new(v, shared)
+    }
+}
+
+/// Unlike `Receiver::into_inner`, this discards any error that the
+/// Receiver was holding on to.
+impl <T, E> From<Receiver<T, E>> for mpsc::Receiver<CommMsg<T, E>>
+where T: Send + 'static, E: Send + 'static {
+    fn from(v: Receiver<T, E>) -> mpsc::Receiver<CommMsg<T, E>> {
+        v.into_inner().0
+    }
+}
+
+impl <'a, T, E> Iterator for ResultIterator<'a, T, E>
+where T: Send + 'static, E: Send + 'static {
+    type Item = Result<T, E>;
+    fn next(&mut self) -> Option<Result<T, E>> {
+        let receiver = self.reference.borrow();
+        let msg = if self.blocking {
+            receiver.recv_msg_block()
+        } else {
+            receiver.recv_msg()
+        };
+        match msg {
+            Some(CommMsg::Message(m)) => Some(Ok(m)),
+            Some(CommMsg::Error(e)) => Some(Err(e)),
+            Some(CommMsg::Barrier(_)) | Some(CommMsg::CloseWith(_)) => unreachable!(),
+            Some(CommMsg::Close) | None => None
+        }
+    }
+}
+
+impl <'a, T, E> ReceiverIterator<'a, T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Returns the error that closed the channel, if there was one.
+    ///
+    /// This is how to get the error back out of the iterators returned by
+    /// `into_iter` and `into_blocking_iter`, which own their receiver.  For
+    /// an iterator that borrows its receiver, this is the same as calling
+    /// `take_error` on the receiver, which can still be done after the
+    /// iterator is gone.
+    pub fn into_error(self) -> Option<E> {
+        self.reference.borrow().take_error()
+    }
+
+    /// Returns an iterator that yields the same messages, but counts them
+    /// and records how the iteration ended.
+    pub fn counted(self) -> CountedIterator<'a, T, E> {
+        CountedIterator {
+            iter: self,
+            yielded: 0,
+            terminated_by: None
+        }
+    }
+}
+
+impl <'a, T, E> CountedIterator<'a, T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Returns the number of messages yielded so far.
+    pub fn yielded_count(&self) -> usize {
+        self.yielded
+    }
+
+    /// Returns how the channel was closed once the iterator has returned
+    /// None because of it.
+    ///
+    /// This is None while the iterator is still going, or if a non-blocking
+    /// iterator ran out of messages while the channel is still open.
+    pub fn terminated_by(&self) -> Option<CloseReason> {
+        self.terminated_by
+    }
+
+    /// Returns the error that closed the channel, if there was one.
+    pub fn into_error(self) -> Option<E> {
+        self.iter.into_error()
+    }
+}
+
+impl <'a, T, E> Iterator for CountedIterator<'a, T, E>
+where T: Send + 'static, E: Send + 'static {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        let m = self.iter.next();
+        match m {
+            Some(_) => self.yielded += 1,
+            None => self.terminated_by = self.iter.reference.borrow().close_reason()
+        }
+        m
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl <'a, T, E> Iterator for ReceiverIterator<'a, T, E>
 where T: Send + 'static, E: Send + 'static {
     type Item = T;
     fn next(&mut self) -> Option<T> {
-        if self.blocking {
-            self.reference.borrow().recv_block()
+        if self.done {
+            return None
+        }
+        let receiver = self.reference.borrow();
+        let msg = if self.blocking {
+            receiver.recv_block()
+        } else {
+            receiver.recv()
+        };
+        if msg.is_none() && receiver.is_closed() {
+            self.done = true;
+        }
+        msg
+    }
+
+    /// Nothing is known about how many messages are left until the
+    /// channel is seen closed: `Receiver::len` counts queued errors too,
+    /// other threads can receive the queued messages first, and senders can
+    /// keep sending while it iterates.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done {
+            (0, Some(0))
         } else {
-            self.reference.borrow().recv()
+            (0, None)
+        }
+    }
+}
+
+impl <'a, T, E> TimeoutIterator<'a, T, E>
+where T: Send + 'static, E: Send + 'static {
+    /// Returns why the iterator last returned None, or None if it hasn't
+    /// stopped.
+    pub fn stopped_reason(&self) -> Option<StopReason> {
+        self.stopped
+    }
+}
+
+impl <'a, T, E> Iterator for TimeoutIterator<'a, T, E>
+where T: Send + 'static, E: Send + 'static {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        let m = self.receiver.recv_timeout(self.idle);
+        self.stopped = match m {
+            Some(_) => None,
+            None if self.receiver.is_closed() => Some(StopReason::Closed),
+            None => Some(StopReason::Idle)
+        };
+        m
+    }
+}
+
+impl <'a, T, E> Iterator for UntilValueIterator<'a, T, E>
+where T: Send + PartialEq + 'static, E: Send + 'static {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.done {
+            return None
+        }
+        match self.receiver.recv_block() {
+            Some(ref m) if *m == self.poison => {
+                self.done = true;
+                None
+            }
+            Some(m) => Some(m),
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl <'a, T, E> Iterator for DedupIterator<'a, T, E>
+where T: Send + PartialEq + Clone + 'static, E: Send + 'static {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        loop {
+            let m = self.inner.next()?;
+            if self.last.as_ref() != Some(&m) {
+                self.last = Some(m.clone());
+                return Some(m)
+            }
+        }
+    }
+}
+
+impl <'a, T, E, F> Iterator for UntilIterator<'a, T, E, F>
+where T: Send + 'static, E: Send + 'static, F: FnMut(&T) -> bool {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.done {
+            return None
+        }
+        match self.receiver.recv_block() {
+            Some(m) if (self.stop)(&m) => {
+                self.receiver.unrecv(m);
+                self.done = true;
+                None
+            }
+            Some(m) => Some(m),
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl <T: Send, E: Send> fmt::Debug for Sender<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("Sender");
+        if let Some(label) = self.label {
+            d.field("label", &label);
+        }
+        d.field("closed", &self.closed.get())
+         .finish()
+    }
+}
+
+impl <T: Send, E: Send> fmt::Debug for Receiver<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("Receiver");
+        if let Some(label) = self.label {
+            d.field("label", &label);
         }
+        d.field("closed", &self.closed.load(Ordering::SeqCst))
+         .field("errored", &self.errored.load(Ordering::SeqCst))
+         .finish()
     }
 }
 
-unsafe impl <T: Send, E: Send> Send for Receiver<T, E> {}
 unsafe impl <T: Send, E: Send> Send for Sender<T, E> {}
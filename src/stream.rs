@@ -0,0 +1,100 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use futures_core::Stream;
+
+use super::{CommMsg, Receiver, channel};
+
+/// A `futures::Stream` over the messages of a receiver.
+///
+/// `std::sync::mpsc` has no way to register a waker, so this spawns one
+/// thread that blocks on the original receiver and forwards every message
+/// into a new channel, waking the task that last polled the stream each
+/// time.  That thread stays blocked until the original channel closes,
+/// even if the stream is dropped first.
+///
+/// The stream ends when the channel is closed.  If it was closed with an
+/// error, the error can be retrieved with `take_error` on the wrapped
+/// receiver (see `get_ref` and `into_inner`).
+pub struct BchannelStream<T: Send, E: Send> {
+    receiver: Receiver<T, E>,
+    waker: Arc<Mutex<Option<Waker>>>
+}
+
+impl <T, E> BchannelStream<T, E>
+where T: Send + 'static, E: Send + 'static {
+    pub(crate) fn new(source: Receiver<T, E>) -> BchannelStream<T, E> {
+        let (tx, rx) = channel();
+        let waker: Arc<Mutex<Option<Waker>>> = Arc::new(Mutex::new(None));
+        let thread_waker = waker.clone();
+
+        thread::spawn(move || {
+            let wake = || {
+                if let Some(w) = thread_waker.lock().unwrap().take() {
+                    w.wake();
+                }
+            };
+            loop {
+                let more = match source.recv_msg_block() {
+                    Some(m @ CommMsg::Message(_)) => tx.send_msg(m).is_ok(),
+                    Some(m) => {
+                        let _ = tx.send_msg(m);
+                        false
+                    }
+                    None => false
+                };
+                if !more {
+                    drop(tx);
+                    wake();
+                    break;
+                }
+                wake();
+            }
+        });
+
+        BchannelStream {
+            receiver: rx,
+            waker
+        }
+    }
+
+    /// Returns a reference to the receiver that this stream reads from.
+    pub fn get_ref(&self) -> &Receiver<T, E> {
+        &self.receiver
+    }
+
+    /// Returns the receiver that this stream reads from.
+    pub fn into_inner(self) -> Receiver<T, E> {
+        self.receiver
+    }
+}
+
+// The stream is never pinned structurally, so the message and error types
+// don't need to be `Unpin`.
+impl <T: Send, E: Send> Unpin for BchannelStream<T, E> {}
+
+impl <T, E> Stream for BchannelStream<T, E>
+where T: Send + 'static, E: Send + 'static {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        let this = self.get_mut();
+        if let Some(m) = this.receiver.recv() {
+            return Poll::Ready(Some(m));
+        }
+        if this.receiver.is_closed() {
+            return Poll::Ready(None);
+        }
+
+        // Register before checking again so that a message forwarded in
+        // between isn't missed.
+        *this.waker.lock().unwrap() = Some(cx.waker().clone());
+        match this.receiver.recv() {
+            Some(m) => Poll::Ready(Some(m)),
+            None if this.receiver.is_closed() => Poll::Ready(None),
+            None => Poll::Pending
+        }
+    }
+}
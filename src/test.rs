@@ -1,4 +1,8 @@
-use super::{Sender, Receiver, channel};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Sender, Receiver, MessageChannel, Broadcaster, RecvStatus, ReceiverStatus, CommMsg, Either, Terminal, SendTimeoutError, SendErr, TrySendError, SendEvent, RecvTimeoutError, SendResultError, SendAllStop, ReserveError, CloseReason, CancelToken, StopReason, channel, channel_with_capacity_hint, sync_channel, select2, merge};
 
 #[test]
 fn basic() {
@@ -8,9 +12,9 @@ fn basic() {
     sx.send(6usize).unwrap();
     sx.close();
 
-    assert!(rx.recv() == Some(5usize));
-    assert!(rx.recv() == Some(6usize));
-    assert!(rx.recv() == None);
+    assert_eq!(rx.recv(), Some(5usize));
+    assert_eq!(rx.recv(), Some(6usize));
+    assert!(rx.recv().is_none());
     assert!(rx.is_closed());
 }
 
@@ -21,11 +25,104 @@ fn error() {
     sx.send(5usize).unwrap();
     sx.error("hi".to_string()).unwrap();
 
-    assert!(rx.recv() == Some(5usize));
-    assert!(rx.recv() == None);
+    assert_eq!(rx.recv(), Some(5usize));
+    assert!(rx.recv().is_none());
+    assert!(rx.is_closed());
+    assert!(rx.has_error());
+    assert_eq!(rx.take_error(), Some("hi".to_string()))
+}
+
+#[test]
+fn messages_after_error() {
+    let (sx, rx) = channel();
+    let sx2 = sx.clone();
+
+    sx.send(1usize).unwrap();
+    sx.send_error("hi".to_string()).unwrap();
+    sx2.send(2usize).unwrap();
+
+    assert_eq!(rx.recv(), Some(1usize));
+    assert!(!rx.is_closed());
+    assert_eq!(rx.recv_block(), Some(2usize));
+    assert_eq!(rx.recv_block(), None);
+    assert!(rx.is_closed());
+    assert_eq!(rx.close_reason(), Some(CloseReason::Error));
+    assert_eq!(rx.take_error(), Some("hi".to_string()))
+}
+
+#[test]
+fn recv_with() {
+    let (sx, rx) = channel::<Vec<u8>, ()>();
+
+    sx.send(vec![1, 2, 3]).unwrap();
+    assert_eq!(rx.recv_with(|v| v.len()), Some(3));
+    assert_eq!(rx.recv_with(|v| v.len()), None);
+
+    sx.error(()).unwrap();
+    assert_eq!(rx.recv_with(|v| v.len()), None);
+    assert!(rx.is_closed());
+    assert_eq!(rx.take_error(), Some(()));
+}
+
+#[test]
+fn poll_error() {
+    let (sx, rx) = channel();
+
+    sx.send(1usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    assert!(!rx.poll_error());
+    assert!(!rx.poll_error());
+    assert_eq!(rx.recv(), Some(1usize));
+    assert!(rx.poll_error());
+    assert!(rx.is_closed());
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+}
+
+#[test]
+fn shared_receiver() {
+    let (sx, rx) = channel::<usize, ()>();
+    let rx = rx.into_shared();
+
+    let workers: Vec<_> = (0..2).map(|_| {
+        let rx = rx.clone();
+        thread::spawn(move || {
+            let mut got = Vec::new();
+            while let Some(m) = rx.recv_block() {
+                got.push(m);
+            }
+            got
+        })
+    }).collect();
+
+    for i in 0..100 {
+        sx.send(i).unwrap();
+    }
+    sx.error(()).unwrap();
+
+    let mut all: Vec<usize> = workers.into_iter().flat_map(|w| w.join().unwrap()).collect();
+    all.sort();
+    assert_eq!(all, (0..100).collect::<Vec<_>>());
     assert!(rx.is_closed());
     assert!(rx.has_error());
-    assert!(rx.take_error() == Some("hi".to_string()))
+    assert_eq!(rx.take_error(), Some(()));
+}
+
+#[test]
+fn try_send_all() {
+    let (sx, rx) = sync_channel::<usize, ()>(2);
+
+    let rest = match sx.try_send_all(0..5) {
+        Err(SendAllStop::Full(2, rest)) => rest,
+        other => panic!("unexpected {:?}", other)
+    };
+    assert_eq!(rx.drain(), vec![0, 1]);
+    assert!(sx.try_send_all(Some(2).into_iter().chain(rest)).is_err());
+    assert_eq!(rx.drain(), vec![2, 3]);
+
+    drop(rx);
+    assert!(matches!(sx.try_send_all(5..7), Err(SendAllStop::Disconnected(5, _))));
 }
 
 #[test]
@@ -39,14 +136,14 @@ fn iter() {
     let mut rx = rx.iter();
 
     let xs: Vec<usize> = rx.by_ref().collect();
-    assert!(xs == vec![5,7,9]);
+    assert_eq!(xs, vec![5,7,9]);
 
     sx.send(1).unwrap();
     sx.send(2).unwrap();
     sx.send(3).unwrap();
 
     let ys: Vec<usize> = rx.collect();
-    assert!(ys == vec![1,2,3]);
+    assert_eq!(ys, vec![1,2,3]);
 }
 
 #[test]
@@ -60,14 +157,14 @@ fn into_iter() {
     let mut rx = rx.into_iter();
 
     let xs: Vec<usize> = rx.by_ref().collect();
-    assert!(xs == vec![5,7,9]);
+    assert_eq!(xs, vec![5,7,9]);
 
     sx.send(1).unwrap();
     sx.send(2).unwrap();
     sx.send(3).unwrap();
 
     let ys: Vec<usize> = rx.collect();
-    assert!(ys == vec![1,2,3]);
+    assert_eq!(ys, vec![1,2,3]);
 }
 
 #[test]
@@ -83,7 +180,7 @@ fn iter_block() {
 
         let rx = rx.blocking_iter();
         let xs: Vec<usize> = rx.collect();
-        assert!(xs == vec![5,7,9]);
+        assert_eq!(xs, vec![5,7,9]);
     }
     // error()
     {
@@ -96,6 +193,1983 @@ fn iter_block() {
 
         let rx = rx.blocking_iter();
         let xs: Vec<usize> = rx.collect();
-        assert!(xs == vec![5,7,9]);
+        assert_eq!(xs, vec![5,7,9]);
+    }
+    // close() with other clones alive
+    {
+        let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+        let sx2 = sx.clone();
+
+        sx.send(5usize).unwrap();
+        sx2.send(7usize).unwrap();
+        sx.send(9usize).unwrap();
+        sx.close(); // sx2 is still alive
+
+        let rx = rx.blocking_iter();
+        let xs: Vec<usize> = rx.collect();
+        assert_eq!(xs, vec![5,7,9]);
+    }
+}
+
+#[test]
+fn try_recv() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    assert!(matches!(rx.try_recv(), RecvStatus::Empty));
+
+    sx.send(5usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    assert!(matches!(rx.try_recv(), RecvStatus::Message(5)));
+    assert!(matches!(rx.try_recv(), RecvStatus::Errored(ref e) if e == "hi"));
+    assert!(matches!(rx.try_recv(), RecvStatus::Closed));
+    assert!(rx.is_closed());
+    assert!(!rx.has_error());
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.close();
+    assert!(matches!(rx.try_recv(), RecvStatus::Closed));
+}
+
+#[test]
+fn recv_timeout() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    assert!(rx.recv_timeout(Duration::from_millis(10)).is_none());
+    assert!(!rx.is_closed());
+
+    sx.send(5usize).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Some(5usize));
+
+    sx.error("hi".to_string()).unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(10)).is_none());
+    assert!(rx.is_closed());
+    assert!(rx.has_error());
+}
+
+#[test]
+fn recv_deadline() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    let start = Instant::now();
+    assert!(rx.recv_deadline(start).is_none());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(!rx.is_closed());
+
+    sx.send(5usize).unwrap();
+    assert_eq!(rx.recv_deadline(Instant::now() + Duration::from_millis(10)), Some(5usize));
+
+    sx.close();
+    assert!(rx.recv_deadline(Instant::now() + Duration::from_millis(10)).is_none());
+    assert!(rx.is_closed());
+
+    // Collected errors don't stop the wait.
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    rx.set_collect_errors(true);
+    sx.send_error(()).unwrap();
+    sx.send(6usize).unwrap();
+    assert_eq!(rx.recv_deadline(Instant::now() + Duration::from_millis(10)), Some(6usize));
+    assert_eq!(rx.take_errors(), vec![()]);
+}
+
+#[test]
+fn sync_channel_bound() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+
+    sx.send(5usize).unwrap();
+    let handle = thread::spawn(move || {
+        sx.send(7usize).unwrap();
+        sx.error(()).unwrap();
+    });
+
+    assert_eq!(rx.recv_block(), Some(5usize));
+    assert_eq!(rx.recv_block(), Some(7usize));
+    assert!(rx.recv_block().is_none());
+    assert!(rx.has_error());
+    handle.join().unwrap();
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    drop(rx);
+    assert_eq!(sx.send(5usize), Err(5usize));
+    assert!(sx.is_closed());
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(0);
+    let handle = thread::spawn(move || {
+        sx.send(1usize).unwrap();
+        sx.close();
+    });
+    assert_eq!(rx.recv_block(), Some(1usize));
+    assert_eq!(rx.recv_block(), None);
+    handle.join().unwrap();
+}
+
+#[test]
+fn debug() {
+    struct NotDebug;
+
+    let (sx, rx): (Sender<NotDebug, NotDebug>, Receiver<NotDebug, NotDebug>) = channel();
+    assert_eq!(format!("{:?}", sx), "Sender { closed: false }");
+    assert_eq!(format!("{:?}", rx), "Receiver { closed: false, errored: false }");
+
+    let m: CommMsg<usize, ()> = CommMsg::Message(5);
+    assert_eq!(format!("{:?}", m), "Message(5)");
+    let e: CommMsg<usize, &str> = CommMsg::Error("hi");
+    assert_eq!(format!("{:?}", e), "Error(\"hi\")");
+}
+
+#[test]
+fn len() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let sx2 = sx.clone();
+
+    assert!(rx.is_empty());
+    sx.send(5usize).unwrap();
+    sx2.send(7usize).unwrap();
+    assert_eq!(rx.len(), 2);
+
+    assert_eq!(rx.recv(), Some(5usize));
+    assert_eq!(rx.len(), 1);
+
+    sx2.error(()).unwrap();
+    assert_eq!(rx.len(), 2);
+    assert_eq!(rx.recv_block(), Some(7usize));
+    assert!(rx.recv_block().is_none());
+    assert!(rx.is_empty());
+}
+
+#[test]
+fn recv_msg() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    assert!(rx.recv_msg().is_none());
+
+    sx.send(5usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    assert_eq!(rx.recv_msg(), Some(CommMsg::Message(5)));
+    assert_eq!(rx.recv_msg_block(), Some(CommMsg::Error("hi".to_string())));
+    assert!(rx.recv_msg().is_none());
+    assert!(rx.is_closed());
+    assert!(rx.take_error().is_none());
+}
+
+#[test]
+fn iter_results() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    let v: Result<Vec<usize>, String> = rx.iter_results(true).collect();
+    assert_eq!(v, Err("hi".to_string()));
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+
+    let v: Vec<Result<usize, String>> = rx.iter_results(false).collect();
+    assert_eq!(v, vec![Ok(5), Ok(7)]);
+
+    sx.close();
+    let v: Result<Vec<usize>, String> = rx.iter_results(true).collect();
+    assert_eq!(v, Ok(vec![]));
+}
+
+#[test]
+fn sender_map() {
+    let (sx, rx): (Sender<String, ()>, Receiver<String, ()>) = channel();
+    let sx = sx.map(|x: u32| x.to_string());
+
+    sx.send(5).unwrap();
+    sx.clone().send(7).unwrap();
+    sx.error(()).unwrap();
+
+    let xs: Vec<String> = rx.blocking_iter().collect();
+    assert_eq!(xs, vec!["5".to_string(), "7".to_string()]);
+    assert!(rx.has_error());
+}
+
+#[test]
+fn drain() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    assert!(rx.drain().is_empty());
+
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+    sx.send(9usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    assert_eq!(rx.drain(), vec![5,7,9]);
+    assert!(rx.is_closed());
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+}
+
+#[test]
+fn peek() {
+    let (sx, mut rx): (Sender<String, ()>, Receiver<String, ()>) = channel();
+
+    assert!(rx.peek().is_none());
+
+    sx.send("a".to_string()).unwrap();
+    sx.send("b".to_string()).unwrap();
+    sx.error(()).unwrap();
+
+    assert_eq!(rx.peek(), Some(&"a".to_string()));
+    assert_eq!(rx.peek(), Some(&"a".to_string()));
+    assert_eq!(rx.len(), 3);
+    assert_eq!(rx.recv(), Some("a".to_string()));
+    assert_eq!(rx.peek(), Some(&"b".to_string()));
+    assert_eq!(rx.recv_block(), Some("b".to_string()));
+    assert!(rx.peek().is_none());
+    assert!(rx.is_closed());
+    assert!(rx.has_error());
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn stream() {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    use futures_core::Stream;
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let mut stream = rx.into_stream();
+
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut xs = vec![];
+    loop {
+        match Pin::new(&mut stream).poll_next(&mut cx) {
+            Poll::Ready(Some(x)) => xs.push(x),
+            Poll::Ready(None) => break,
+            Poll::Pending => thread::yield_now()
+        }
+    }
+
+    assert_eq!(xs, vec![5,7]);
+    assert_eq!(stream.get_ref().take_error(), Some("hi".to_string()));
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn poll_recv() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct CountingWaker(AtomicUsize);
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+    sx.send(5).unwrap();
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+    assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(5)));
+
+    assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 2);
+    assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+    drop(sx);
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 3);
+    assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+
+    // A Sender of a channel with a bound of 0 can't wake the task before
+    // the message is taken, so the task is woken to poll again.
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = sync_channel(0);
+    let handle = thread::spawn(move || sx.send(6).unwrap());
+    loop {
+        let before = wakes.0.load(Ordering::SeqCst);
+        match rx.poll_recv(&mut cx) {
+            Poll::Ready(m) => {
+                assert_eq!(m, Some(6));
+                break;
+            }
+            Poll::Pending => assert!(wakes.0.load(Ordering::SeqCst) > before)
+        }
+    }
+    handle.join().unwrap();
+}
+
+#[test]
+fn select() {
+    let (sa, ra): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (sb, rb): (Sender<String, ()>, Receiver<String, ()>) = channel();
+
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        sb.send("b".to_string()).unwrap();
+        sb
+    });
+    assert_eq!(select2(&ra, &rb), Either::Right(Some("b".to_string())));
+
+    // A closed receiver is skipped until both are closed.
+    handle.join().unwrap().error(()).unwrap();
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        sa.send(3).unwrap();
+        sa
+    });
+    assert_eq!(select2(&ra, &rb), Either::Left(Some(3)));
+    assert!(rb.has_error());
+
+    drop(handle.join().unwrap());
+    assert_eq!(select2(&ra, &rb), Either::Left(None));
+    assert!(ra.is_closed());
+}
+
+#[test]
+fn merge_receivers() {
+    let (sa, ra): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let (sb, rb): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let merged = merge(vec![ra, rb]);
+
+    sa.send(1usize).unwrap();
+    sb.send(2usize).unwrap();
+    sa.send(3usize).unwrap();
+    sa.close();
+    sb.close();
+
+    let mut xs: Vec<usize> = merged.blocking_iter().collect();
+    xs.sort();
+    assert_eq!(xs, vec![1,2,3]);
+    assert!(!merged.has_error());
+
+    let (sa, ra): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let (_sb, rb): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let merged = merge(vec![ra, rb]);
+
+    sa.error("hi".to_string()).unwrap();
+    assert!(merged.recv_block().is_none());
+    assert_eq!(merged.take_error(), Some("hi".to_string()));
+}
+
+#[test]
+fn broadcast() {
+    let b: Broadcaster<usize, String> = Broadcaster::new();
+    let r1 = b.subscribe();
+    let r2 = b.subscribe();
+
+    assert_eq!(b.send(5usize), 2);
+    assert_eq!(b.send(7usize), 2);
+    b.error("hi".to_string());
+
+    assert_eq!(r1.blocking_iter().collect::<Vec<usize>>(), vec![5,7]);
+    assert_eq!(r2.blocking_iter().collect::<Vec<usize>>(), vec![5,7]);
+    assert_eq!(r1.take_error(), Some("hi".to_string()));
+    assert_eq!(r2.take_error(), Some("hi".to_string()));
+
+    let r3 = b.subscribe();
+    drop(b.subscribe());
+    assert_eq!(b.send(9usize), 1);
+    assert_eq!(r3.recv(), Some(9usize));
+}
+
+#[test]
+fn send_error() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    sx.send(5usize).unwrap();
+    sx.send_error("first".to_string()).unwrap();
+    sx.send_error("second".to_string()).unwrap();
+    assert_eq!(rx.len(), 3);
+
+    assert_eq!(rx.recv(), Some(5usize));
+    assert!(rx.recv().is_none());
+    assert_eq!(rx.take_error(), Some("first".to_string()));
+
+    drop(rx);
+    assert_eq!(sx.send_error("third".to_string()), Err("third".to_string()));
+}
+
+#[test]
+fn collect_errors() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    rx.set_collect_errors(true);
+
+    sx.send(5usize).unwrap();
+    sx.send_error("first".to_string()).unwrap();
+    sx.send(7usize).unwrap();
+    sx.send_error("second".to_string()).unwrap();
+    sx.close();
+
+    let xs: Vec<usize> = rx.blocking_iter().collect();
+    assert_eq!(xs, vec![5,7]);
+    assert!(rx.has_error());
+    assert_eq!(rx.take_errors(), vec!["first".to_string(), "second".to_string()]);
+    assert!(!rx.has_error());
+    assert!(rx.take_error().is_none());
+}
+
+#[test]
+fn wait_closed() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    sx.send(5usize).unwrap();
+    assert!(!rx.wait_closed_timeout(Duration::from_millis(10)));
+    assert!(rx.is_empty());
+
+    let handle = thread::spawn(move || {
+        sx.send(7usize).unwrap();
+        sx.error("hi".to_string()).unwrap();
+    });
+    rx.wait_closed();
+    handle.join().unwrap();
+
+    assert!(rx.is_closed());
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+    assert!(rx.wait_closed_timeout(Duration::from_millis(10)));
+}
+
+#[test]
+fn recv_all() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+    assert_eq!(rx.recv_all(), (vec![5,7], None));
+
+    sx.send(9usize).unwrap();
+    sx.close();
+    assert_eq!(rx.recv_all(), (vec![9], Some(Terminal::Closed)));
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    sx.send(5usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(rx.recv_all(), (vec![5], Some(Terminal::Errored("hi".to_string()))));
+    assert!(!rx.has_error());
+}
+
+#[test]
+fn send_all_counted() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    assert_eq!(sx.send_all_counted(vec![5usize, 7, 9].into_iter()).ok(), Some(3));
+    assert_eq!(sx.send_all_counted(Vec::new().into_iter()).ok(), Some(0));
+    assert_eq!(rx.drain(), vec![5,7,9]);
+
+    drop(rx);
+    match sx.send_all_counted(vec![1usize, 2, 3].into_iter()) {
+        Err((sent, x, rest)) => {
+            assert_eq!(sent, 0);
+            assert_eq!(x, 1);
+            assert_eq!(rest.collect::<Vec<usize>>(), vec![2,3]);
+        }
+        Ok(_) => panic!("sending should have failed")
+    }
+}
+
+#[test]
+fn from_mpsc() {
+    use std::convert::TryFrom;
+
+    let (tx, rx) = mpsc::channel();
+    let sx: Sender<usize, ()> = tx.into();
+    let rx: Receiver<usize, ()> = rx.into();
+
+    sx.send(5usize).unwrap();
+    assert_eq!(rx.recv(), Some(5usize));
+
+    let tx = mpsc::Sender::try_from(sx).unwrap();
+    let rx: mpsc::Receiver<CommMsg<usize, ()>> = rx.into();
+    tx.send(CommMsg::Message(7usize)).unwrap();
+    assert_eq!(rx.recv(), Ok(CommMsg::Message(7)));
+
+    let (sx, _rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    assert!(mpsc::Sender::try_from(sx).is_err());
+}
+
+#[test]
+fn sender_into_inner() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let tx = sx.into_inner().unwrap();
+    tx.send(CommMsg::Message(5)).unwrap();
+    assert_eq!(rx.recv_block(), Some(5));
+
+    // Messages sent on the old-style Sender still wake a blocked receive.
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        tx.send(CommMsg::Message(6)).unwrap();
+    });
+    assert_eq!(rx.recv_block(), Some(6));
+    handle.join().unwrap();
+    assert_eq!(rx.recv_block(), None);
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    let sx = sx.into_inner().unwrap_err();
+    sx.send(1).unwrap();
+    assert_eq!(rx.recv(), Some(1));
+    assert!(Sender::<usize, ()>::null().into_inner().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn comm_msg_serde() {
+    use serde_json;
+
+    let msgs: Vec<CommMsg<usize, String>> = vec![
+        CommMsg::Message(5),
+        CommMsg::Error("broken".to_string()),
+        CommMsg::Close,
+        CommMsg::CloseWith("done".to_string()),
+    ];
+    for m in msgs {
+        let json = serde_json::to_string(&m).unwrap();
+        let back: CommMsg<usize, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, m);
     }
+
+    let e = serde_json::to_string(&CommMsg::<usize, String>::Error("broken".to_string())).unwrap();
+    assert_eq!(e, r#"{"Error":"broken"}"#);
+}
+
+#[test]
+fn recv_checked() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    assert_eq!(rx.recv_checked(), Ok(None));
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    assert_eq!(rx.recv_checked(), Ok(Some(5usize)));
+    assert_eq!(rx.recv_checked_block(), Ok(Some(7usize)));
+    assert_eq!(rx.recv_checked_block(), Err("hi".to_string()));
+    assert_eq!(rx.recv_checked_block(), Ok(None));
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn recv_block_checked() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    let worker = thread::spawn(move || {
+        let mut got = Vec::new();
+        let end = loop {
+            match rx.recv_block_checked() {
+                Ok(Some(m)) => got.push(m),
+                other => break other
+            }
+        };
+        (got, end, rx.is_closed(), rx.close_reason())
+    });
+
+    sx.send(1usize).unwrap();
+    sx.send(2usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    let (got, end, closed, reason) = worker.join().unwrap();
+    assert_eq!(got, vec![1, 2]);
+    assert_eq!(end, Err("hi".to_string()));
+    assert!(closed);
+    assert_eq!(reason, Some(CloseReason::Error));
+}
+
+#[test]
+fn collect_results() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    sx.send(1usize).unwrap();
+    sx.send(2usize).unwrap();
+    drop(sx);
+    assert_eq!(rx.collect_results(), Ok(vec![1, 2]));
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    sx.send(1usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(rx.collect_results(), Err("hi".to_string()));
+}
+
+#[test]
+fn on_close() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let (done_tx, done_rx) = mpsc::channel();
+    rx.on_close(move |e| done_tx.send(e).unwrap());
+
+    sx.send(1usize).unwrap();
+    assert!(done_rx.recv_timeout(Duration::from_millis(20)).is_err());
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(done_rx.recv(), Ok(Some("hi".to_string())));
+}
+
+#[test]
+fn sender_sees_dropped_receiver() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let sx2 = sx.clone();
+
+    assert!(!sx.is_closed());
+    drop(rx);
+    assert!(sx.is_closed());
+    assert!(sx2.is_closed());
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (old, _) = rx.into_inner();
+    assert!(!sx.is_closed());
+    drop(old);
+    assert!(sx.send(1).is_err());
+    assert!(sx.is_closed());
+}
+
+#[test]
+fn timeout_iter() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    let producer = thread::spawn(move || {
+        sx.send(1).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        sx.send(2).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        sx
+    });
+
+    let mut it = rx.timeout_iter(Duration::from_millis(100));
+    assert_eq!(it.stopped_reason(), None);
+    assert_eq!(it.by_ref().collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(it.stopped_reason(), Some(StopReason::Idle));
+
+    drop(producer.join().unwrap());
+    assert_eq!(it.next(), None);
+    assert_eq!(it.stopped_reason(), Some(StopReason::Closed));
+}
+
+#[test]
+fn clear_error() {
+    let (sx, rx) = channel();
+
+    sx.send(1usize).unwrap();
+    sx.send_error("warning".to_string()).unwrap();
+    sx.send(2usize).unwrap();
+
+    assert_eq!(rx.recv(), Some(1));
+    assert_eq!(rx.recv(), Some(2));
+    assert_eq!(rx.recv(), None);
+    assert!(rx.is_closed());
+
+    assert_eq!(rx.clear_error(), Some("warning".to_string()));
+    assert!(!rx.is_closed());
+    assert!(!rx.has_error());
+    sx.send(3usize).unwrap();
+    assert_eq!(rx.recv(), Some(3));
+
+    sx.send_error("fatal".to_string()).unwrap();
+    drop(sx);
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.clear_error(), Some("fatal".to_string()));
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn try_send() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    assert_eq!(sx.try_send(1), Ok(()));
+    assert_eq!(rx.recv(), Some(1));
+    drop(rx);
+    assert_eq!(sx.try_send(2), Err(SendErr::Disconnected(2)));
+    assert_eq!(sx.try_send(3), Err(SendErr::Closed(3)));
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    assert_eq!(sx.try_send(1), Ok(()));
+    assert_eq!(sx.try_send(2), Err(SendErr::Full(2)));
+    assert_eq!(rx.recv(), Some(1));
+    assert_eq!(sx.try_send(3), Ok(()));
+}
+
+#[test]
+fn send_batch() {
+    let (sx, rx): (Sender<String, ()>, Receiver<String, ()>) = channel();
+
+    let mut items = vec!["a".to_string(), "b".to_string()];
+    assert_eq!(sx.send_batch(&mut items), Ok(()));
+    assert!(items.is_empty());
+    assert_eq!(rx.drain(), vec!["a".to_string(), "b".to_string()]);
+
+    drop(rx);
+    let mut items = vec!["c".to_string(), "d".to_string()];
+    assert_eq!(sx.send_batch(&mut items), Err(0));
+    assert_eq!(items, vec!["c".to_string(), "d".to_string()]);
+}
+
+#[test]
+fn into_parts() {
+    let (sx, mut rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    sx.send(1).unwrap();
+    sx.send(2).unwrap();
+    assert_eq!(rx.peek(), Some(&1));
+
+    let (old, error, peeked) = rx.into_parts();
+    assert_eq!(error, None);
+    assert_eq!(peeked, vec![1]);
+    assert_eq!(old.try_recv(), Ok(CommMsg::Message(2)));
+}
+
+#[test]
+fn weak_sender() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let weak = sx.downgrade();
+    let sx2 = sx.clone();
+
+    weak.upgrade().unwrap().send(1).unwrap();
+    assert_eq!(rx.sender_count(), 2);
+    drop(sx);
+    drop(sx2);
+
+    assert!(weak.upgrade().is_none());
+    assert_eq!(rx.recv_block(), Some(1));
+    assert_eq!(rx.recv_block(), None);
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn recv_timeout_raw() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let short = Duration::from_millis(10);
+
+    assert_eq!(rx.recv_timeout_raw(short), Err(RecvTimeoutError::Timeout));
+    sx.send(1).unwrap();
+    assert_eq!(rx.recv_timeout_raw(short), Ok(1));
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(rx.recv_timeout_raw(short), Err(RecvTimeoutError::Errored("hi".to_string())));
+    assert_eq!(rx.recv_timeout_raw(short), Err(RecvTimeoutError::Disconnected));
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    drop(sx);
+    assert_eq!(rx.recv_timeout_raw(short), Err(RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn labels() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let sx = sx.with_label("input");
+    let rx = rx.with_label("worker");
+
+    assert_eq!(sx.clone().label(), Some("input"));
+    assert_eq!(sx.downgrade().upgrade().unwrap().label(), Some("input"));
+    assert_eq!(rx.label(), Some("worker"));
+    assert_eq!(format!("{:?}", sx), "Sender { label: \"input\", closed: false }");
+    assert_eq!(format!("{:?}", rx), "Receiver { label: \"worker\", closed: false, errored: false }");
+}
+
+#[test]
+fn recv_spin() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    sx.send(1).unwrap();
+    assert_eq!(rx.recv_spin(0), Some(1));
+    sx.send(2).unwrap();
+    assert_eq!(rx.recv_spin(100), Some(2));
+
+    let producer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        sx.send(3).unwrap();
+    });
+    assert_eq!(rx.recv_spin(10), Some(3));
+    producer.join().unwrap();
+    assert_eq!(rx.recv_spin(10), None);
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn drain_after_disconnect() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send_all(1..4).unwrap();
+    drop(sx);
+    assert_eq!(rx.recv(), Some(1));
+    assert_eq!(rx.recv_block(), Some(2));
+    assert_eq!(rx.recv(), Some(3));
+    assert_eq!(rx.recv(), None);
+    assert!(rx.is_closed());
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(3);
+    sx.send_all(1..4).unwrap();
+    drop(sx);
+    assert_eq!(rx.blocking_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn barrier() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    sx.send(1).unwrap();
+    let token = sx.barrier();
+    sx.send(2).unwrap();
+
+    let (done_tx, done_rx) = mpsc::channel();
+    let waiter = thread::spawn(move || done_tx.send(token.wait()).unwrap());
+
+    assert_eq!(rx.recv(), Some(1));
+    assert!(done_rx.recv_timeout(Duration::from_millis(20)).is_err());
+    assert_eq!(rx.recv(), Some(2));
+    assert_eq!(done_rx.recv(), Ok(true));
+    assert_eq!(rx.len(), 0);
+    waiter.join().unwrap();
+
+    let token = sx.barrier();
+    drop(rx);
+    assert!(!token.wait());
+}
+
+#[test]
+fn iter_size_hint() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send_all(0..3).unwrap();
+
+    assert_eq!(rx.iter().size_hint(), (0, None));
+    assert_eq!(rx.blocking_iter().size_hint(), (0, None));
+
+    // A queued error isn't yielded, so it can't count towards the hint.
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send(1).unwrap();
+    sx.send_error(()).unwrap();
+    let mut it = rx.iter();
+    let (lower, _) = it.size_hint();
+    assert!(lower <= 1);
+    assert_eq!(it.next(), Some(1));
+    assert_eq!(it.next(), None);
+    assert_eq!(it.size_hint(), (0, Some(0)));
+}
+
+#[test]
+fn message_channel() {
+    let (sx, rx): MessageChannel<usize> = super::message_channel();
+
+    sx.send(1).unwrap();
+    sx.close();
+    assert_eq!(rx.recv(), Some(1));
+    assert_eq!(rx.recv(), None);
+    assert!(rx.is_closed());
+    assert!(!rx.has_error());
+}
+
+#[test]
+fn status() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    sx.send_all(0..2).unwrap();
+    sx.error(()).unwrap();
+    assert_eq!(rx.status(), ReceiverStatus { closed: false, errored: false, len: 3 });
+    rx.drain();
+    assert_eq!(rx.status(), ReceiverStatus { closed: true, errored: true, len: 0 });
+}
+
+#[test]
+fn status_is_consistent() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let rx = Arc::new(rx);
+    let rx2 = rx.clone();
+    let receiver = thread::spawn(move || rx2.wait_closed());
+
+    sx.send_all(0..100).unwrap();
+    sx.error(()).unwrap();
+    loop {
+        let status = rx.status();
+        if status.closed {
+            // The error is stored by the time the channel shows as closed.
+            assert!(status.errored);
+            break;
+        }
+    }
+    receiver.join().unwrap();
+}
+
+#[test]
+fn receiver_is_sync() {
+    fn assert_sync<T: Sync>() {}
+    fn assert_send<T: Send>() {}
+    assert_sync::<Receiver<usize, String>>();
+    assert_send::<Receiver<usize, String>>();
+    // The messages don't have to be Sync, only the error does.
+    assert_sync::<Receiver<::std::cell::Cell<usize>, ()>>();
+    assert_send::<Receiver<usize, ::std::cell::Cell<usize>>>();
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let rx = Arc::new(rx);
+    let rx2 = rx.clone();
+    let watcher = thread::spawn(move || {
+        while !rx2.is_closed() {
+            thread::yield_now();
+        }
+    });
+    sx.send(1).unwrap();
+    drop(sx);
+    assert_eq!(rx.recv_block(), Some(1));
+    assert_eq!(rx.recv_block(), None);
+    watcher.join().unwrap();
+}
+
+#[test]
+fn recv_while_another_thread_blocks() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let rx = Arc::new(rx);
+    let rx2 = rx.clone();
+    let blocked = thread::spawn(move || rx2.recv_block());
+    thread::sleep(Duration::from_millis(20));
+
+    // None of these wait for the blocked thread to give up the queue.
+    assert_eq!(rx.recv(), None);
+    assert!(!rx.poll_error());
+    assert_eq!(rx.status(), ReceiverStatus { closed: false, errored: false, len: 0 });
+
+    sx.send(1).unwrap();
+    assert_eq!(blocked.join().unwrap(), Some(1));
+}
+
+#[test]
+fn recv_while_another_thread_checks_status() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let (sa, ra): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (_sb, rb): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let ra = Arc::new(ra);
+    let stop = Arc::new(AtomicBool::new(false));
+    let checker = {
+        let ra = ra.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                ra.status();
+            }
+        })
+    };
+
+    for i in 0..100 {
+        sa.send(i).unwrap();
+        assert_eq!(ra.recv(), Some(i));
+    }
+    for i in 0..10 {
+        let handle = {
+            let sa = sa.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(1));
+                sa.send(i).unwrap();
+            })
+        };
+        assert_eq!(select2(&*ra, &rb), Either::Left(Some(i)));
+        handle.join().unwrap();
+    }
+    stop.store(true, Ordering::SeqCst);
+    checker.join().unwrap();
+}
+
+#[test]
+fn error_wakes_blocked_thread() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let rx = Arc::new(rx);
+    let rx2 = rx.clone();
+    let blocked = thread::spawn(move || rx2.recv_block());
+    thread::sleep(Duration::from_millis(20));
+
+    rx.inject_error(());
+    assert_eq!(blocked.join().unwrap(), None);
+    assert!(rx.has_error());
+    assert!(!sx.is_closed());
+}
+
+#[test]
+fn send_err() {
+    use std::error::Error;
+
+    fn send_twice(sx: &Sender<usize, ()>) -> Result<(), Box<dyn Error>> {
+        sx.send_err(1)?;
+        sx.send_err(2)?;
+        Ok(())
+    }
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    assert!(send_twice(&sx).is_ok());
+    assert_eq!(rx.drain(), vec![1, 2]);
+
+    drop(rx);
+    let e = sx.send_err(3).unwrap_err();
+    assert_eq!(e.to_string(), "sending on a closed channel");
+    assert_eq!(e.into_inner(), 3);
+    assert_eq!(send_twice(&sx).unwrap_err().to_string(), "sending on a closed channel");
+}
+
+#[test]
+fn counted_iter() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send_all(0..3).unwrap();
+
+    let mut it = rx.iter().counted();
+    assert_eq!(it.by_ref().count(), 3);
+    assert_eq!(it.yielded_count(), 3);
+    assert_eq!(it.terminated_by(), None);
+
+    sx.send(3).unwrap();
+    sx.error(()).unwrap();
+    let mut it = rx.into_blocking_iter().counted();
+    assert_eq!(it.by_ref().collect::<Vec<_>>(), vec![3]);
+    assert_eq!(it.yielded_count(), 1);
+    assert_eq!(it.terminated_by(), Some(CloseReason::Error));
+    assert_eq!(it.into_error(), Some(()));
+}
+
+#[test]
+fn capacity_hint() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel_with_capacity_hint(16);
+    sx.send_all(0..32).unwrap();
+    assert_eq!(rx.drain(), (0..32).collect::<Vec<_>>());
+}
+
+#[test]
+fn merge_fair() {
+    let (flood, flood_rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (trickle, trickle_rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    flood.send_all(0..1000).unwrap();
+    trickle.send(1000).unwrap();
+    let rx = super::merge_fair(vec![flood_rx, trickle_rx]);
+
+    let first: Vec<usize> = rx.blocking_iter().take(2).collect();
+    assert_eq!(first, vec![0, 1000]);
+
+    trickle.send(1001).unwrap();
+    drop(trickle);
+    drop(flood);
+    let rest: Vec<usize> = rx.blocking_iter().collect();
+    assert_eq!(rest.len(), 1000);
+    assert!(rest.contains(&1001));
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn unrecv() {
+    let (sx, mut rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    sx.send_all(0..3).unwrap();
+    let m = rx.recv().unwrap();
+    rx.unrecv(m);
+    assert_eq!(rx.len(), 3);
+    assert_eq!(rx.recv_block(), Some(0));
+
+    assert_eq!(rx.peek(), Some(&1));
+    rx.unrecv(10);
+    assert_eq!(rx.peek(), Some(&10));
+    drop(sx);
+    assert_eq!(rx.drain(), vec![10, 1, 2]);
+    assert!(rx.is_closed());
+
+    rx.unrecv(11);
+    assert_eq!(rx.recv(), Some(11));
+    assert_eq!(rx.recv(), None);
+}
+
+#[test]
+fn close_with() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let sx2 = sx.clone();
+
+    sx.send(1).unwrap();
+    sx.close_with("end of stream".to_string());
+    assert_eq!(rx.recv_block(), Some(1));
+    assert_eq!(rx.recv_block(), None);
+    assert!(rx.is_closed());
+    assert!(!rx.has_error());
+    assert_eq!(rx.close_reason(), Some(CloseReason::Clean));
+    assert_eq!(rx.take_close_value(), Some("end of stream".to_string()));
+    assert_eq!(rx.take_close_value(), None);
+    drop(sx2);
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let rx = rx.map(|m| m + 1);
+    sx.close_with("done".to_string());
+    assert_eq!(rx.recv_block(), None);
+    assert_eq!(rx.take_close_value(), Some("done".to_string()));
+}
+
+#[test]
+fn std_style_recv() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    assert_eq!(rx.std_try_recv(), Err(mpsc::TryRecvError::Empty));
+    sx.send(1).unwrap();
+    sx.send(2).unwrap();
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(rx.std_try_recv(), Ok(1));
+    assert_eq!(rx.std_recv(), Ok(2));
+    assert_eq!(rx.std_recv(), Err(mpsc::RecvError));
+    assert_eq!(rx.std_try_recv(), Err(mpsc::TryRecvError::Disconnected));
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+}
+
+#[test]
+fn clones() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    let senders = sx.clones(5);
+    assert_eq!(rx.sender_count(), 6);
+    drop(sx);
+    for (i, s) in senders.iter().enumerate() {
+        s.send(i).unwrap();
+    }
+    drop(senders);
+    assert_eq!(rx.sender_count(), 0);
+    assert_eq!(rx.blocking_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn request_close() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    let producer = thread::spawn(move || {
+        let mut sent = 0;
+        while !sx.is_shutdown_requested() {
+            sx.send(sent).unwrap();
+            sent += 1;
+            thread::yield_now();
+        }
+        sent
+    });
+
+    assert_eq!(rx.recv_block(), Some(0));
+    rx.request_close();
+    let sent = producer.join().unwrap();
+    assert_eq!(rx.blocking_iter().count(), sent - 1);
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn into_result_iter() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    sx.send_all(0..2).unwrap();
+    drop(sx);
+    assert_eq!(rx.into_result_iter().collect::<Vec<_>>(), vec![Ok(0), Ok(1)]);
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let producer = thread::spawn(move || {
+        sx.send_all(0..2).unwrap();
+        sx.error("hi".to_string()).unwrap();
+    });
+    assert_eq!(rx.into_result_iter().collect::<Vec<_>>(),
+               vec![Ok(0), Ok(1), Err("hi".to_string())]);
+    producer.join().unwrap();
+}
+
+#[test]
+fn full_does_not_close() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+
+    sx.send(1).unwrap();
+    assert!(matches!(sx.try_send_all(Some(2).into_iter()), Err(SendAllStop::Full(2, _))));
+    assert!(!sx.is_closed());
+    assert_eq!(sx.send_timeout(3, Duration::from_millis(5)), Err(SendTimeoutError::Timeout(3)));
+    assert!(!sx.is_closed());
+
+    assert_eq!(rx.recv(), Some(1));
+    sx.send(4).unwrap();
+    assert_eq!(rx.recv(), Some(4));
+}
+
+#[test]
+fn recv_block_cancellable() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let token = CancelToken::new();
+    sx.send(1).unwrap();
+    assert_eq!(rx.recv_block_cancellable(&token), Some(1));
+
+    let canceller = token.clone();
+    let handle = ::std::thread::spawn(move || {
+        ::std::thread::sleep(::std::time::Duration::from_millis(20));
+        canceller.cancel();
+    });
+    assert_eq!(rx.recv_block_cancellable(&token), None);
+    assert!(token.is_cancelled());
+    assert!(!rx.is_closed());
+    handle.join().unwrap();
+
+    sx.send(2).unwrap();
+    assert_eq!(rx.recv_block(), Some(2));
+    drop(sx);
+    assert_eq!(rx.recv_block_cancellable(&CancelToken::new()), None);
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn fold_block() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send(5).unwrap();
+    sx.send(7).unwrap();
+    sx.send(9).unwrap();
+    drop(sx);
+    assert_eq!(rx.fold_block(0, |a, b| a + b), (21, None));
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send(5).unwrap();
+    sx.error(()).unwrap();
+    assert_eq!(rx.fold_block(0, |a, b| a + b), (5, Some(())));
+}
+
+#[test]
+fn iter_latches_on_close() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let mut it = rx.iter();
+    assert_eq!(it.next(), None);
+    sx.send(1).unwrap();
+    assert_eq!(it.next(), Some(1));
+
+    sx.error(()).unwrap();
+    assert_eq!(it.next(), None);
+    assert_eq!(it.size_hint(), (0, Some(0)));
+    assert_eq!(it.next(), None);
+}
+
+#[test]
+fn send2() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    assert_eq!(sx.send2(1), Ok(()));
+    assert_eq!(sx.send2(2), Err(TrySendError::Full(2)));
+    assert_eq!(rx.recv(), Some(1));
+    assert_eq!(sx.send2(3), Ok(()));
+    drop(rx);
+    let err = sx.send2(4).unwrap_err();
+    assert_eq!(err, TrySendError::Disconnected(4));
+    assert_eq!(err.to_string(), "sending on a closed channel");
+    assert_eq!(err.into_inner(), 4);
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    assert_eq!(sx.send2(1), Ok(()));
+    assert_eq!(rx.recv(), Some(1));
+}
+
+#[test]
+fn with_error() {
+    let (sx, rx): (Sender<(), String>, Receiver<(), String>) = channel();
+    assert!(rx.with_error(|e| e.is_none()));
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.with_error(|e| e.cloned()), Some("hi".to_string()));
+    assert_eq!(rx.with_error(|e| e.map(|e| e.len())), Some(2));
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+    assert!(rx.with_error(|e| e.is_none()));
+}
+
+#[test]
+fn drain_on_error() {
+    for &drain in &[true, false] {
+        let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+        rx.set_drain_on_error(drain);
+        let sx2 = sx.clone();
+        sx.send(1).unwrap();
+        sx2.error(()).unwrap();
+        sx.send(3).unwrap();
+
+        assert_eq!(rx.recv(), Some(1));
+        if drain {
+            assert_eq!(rx.recv(), Some(3));
+        }
+        assert_eq!(rx.recv(), None);
+        assert!(rx.is_closed());
+        assert_eq!(rx.take_error(), Some(()));
+        assert_eq!(rx.recv(), None);
+    }
+}
+
+#[test]
+fn recv_many() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    for i in 0..5 {
+        sx.send(i).unwrap();
+    }
+    let mut buf = vec![10];
+    assert_eq!(rx.recv_many(&mut buf, 3), 3);
+    assert_eq!(buf, vec![10, 0, 1, 2]);
+    buf.clear();
+    assert_eq!(rx.recv_many(&mut buf, 3), 2);
+    assert_eq!(buf, vec![3, 4]);
+    assert_eq!(rx.recv_many(&mut buf, 3), 0);
+
+    sx.send(5).unwrap();
+    sx.error(()).unwrap();
+    buf.clear();
+    assert_eq!(rx.recv_many(&mut buf, 3), 1);
+    assert_eq!(buf, vec![5]);
+    assert_eq!(rx.take_error(), Some(()));
+}
+
+#[test]
+fn tee() {
+    let (sx1, rx1): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (sx2, rx2): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let tee = sx1.tee(sx2);
+    tee.send(1).unwrap();
+    tee.send(2).unwrap();
+    assert_eq!(rx1.drain(), vec![1, 2]);
+    assert_eq!(rx2.drain(), vec![1, 2]);
+
+    drop(rx1);
+    tee.send(3).unwrap();
+    assert!(!tee.is_closed());
+    assert_eq!(rx2.recv(), Some(3));
+    drop(rx2);
+    assert_eq!(tee.send(4), Err(4));
+    assert!(tee.is_closed());
+
+    let (sx1, rx1): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (sx2, rx2): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx1.tee(sx2).error(()).unwrap();
+    assert_eq!(rx1.recv(), None);
+    assert_eq!(rx2.recv(), None);
+    assert_eq!(rx1.take_error(), Some(()));
+    assert_eq!(rx2.take_error(), Some(()));
+}
+
+#[test]
+fn panicking_consumer_closes_senders() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    let watcher = sx.clone();
+    let producer = ::std::thread::spawn(move || {
+        let mut i = 0;
+        while sx.send(i).is_ok() {
+            i += 1;
+        }
+    });
+
+    let result = panic::catch_unwind(AssertUnwindSafe(move || {
+        for m in rx.blocking_iter() {
+            if m == 3 {
+                panic!("consumer failed");
+            }
+        }
+    }));
+    assert!(result.is_err());
+
+    producer.join().unwrap();
+    assert!(watcher.is_closed());
+    assert_eq!(watcher.send(0), Err(0));
+}
+
+#[test]
+fn iter_until() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    for &m in &[1, 2, 0, 3] {
+        sx.send(m).unwrap();
+    }
+    let xs: Vec<usize> = rx.iter_until(|m| *m == 0).collect();
+    assert_eq!(xs, vec![1, 2]);
+    assert_eq!(rx.recv(), Some(0));
+    assert_eq!(rx.recv(), Some(3));
+
+    sx.send(4).unwrap();
+    drop(sx);
+    let xs: Vec<usize> = rx.iter_until(|m| *m == 0).collect();
+    assert_eq!(xs, vec![4]);
+}
+
+#[test]
+fn null_sender() {
+    let sx: Sender<usize, ()> = Sender::null();
+    for i in 0..100 {
+        assert_eq!(sx.send(i), Ok(()));
+    }
+    assert_eq!(sx.try_send(1), Ok(()));
+    assert_eq!(sx.send2(1), Ok(()));
+    assert_eq!(sx.send_error(()), Ok(()));
+    assert!(!sx.is_closed());
+    assert!(!sx.barrier().wait());
+    assert_eq!(sx.clone().error(()), Ok(()));
+    sx.close();
+}
+
+#[test]
+fn take_block() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    for i in 0..3 {
+        sx.send(i).unwrap();
+    }
+    assert_eq!(rx.take_block(2), vec![0, 1]);
+    sx.send(3).unwrap();
+    sx.close();
+    assert_eq!(rx.take_block(5), vec![2, 3]);
+    assert_eq!(rx.take_block(5), Vec::<usize>::new());
+}
+
+#[test]
+fn clone_error() {
+    let (sx, rx): (Sender<(), String>, Receiver<(), String>) = channel();
+    assert_eq!(rx.clone_error(), None);
+    sx.error("hi".to_string()).unwrap();
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.clone_error(), Some("hi".to_string()));
+    assert_eq!(rx.clone_error(), rx.clone_error());
+    assert!(rx.has_error());
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+    assert_eq!(rx.clone_error(), None);
+}
+
+#[test]
+fn with_observer() {
+    use std::sync::Mutex;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let log = events.clone();
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let sx = sx.with_observer(move |e| log.lock().unwrap().push(e));
+    sx.send(1).unwrap();
+    sx.clone().send(2).unwrap();
+    sx.send_error(()).unwrap();
+    assert_eq!(*events.lock().unwrap(), vec![SendEvent::Sent, SendEvent::Sent, SendEvent::Errored]);
+
+    drop(rx);
+    assert_eq!(sx.send(3), Err(3));
+    sx.close();
+    assert_eq!(&events.lock().unwrap()[3..], &[SendEvent::Failed, SendEvent::Closed]);
+}
+
+#[test]
+fn iter_dedup() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    for &m in &[1, 1, 2, 2, 1] {
+        sx.send(m).unwrap();
+    }
+    let mut it = rx.iter_dedup();
+    let xs: Vec<usize> = it.by_ref().collect();
+    assert_eq!(xs, vec![1, 2, 1]);
+
+    sx.send(1).unwrap();
+    sx.send(3).unwrap();
+    let ys: Vec<usize> = it.collect();
+    assert_eq!(ys, vec![3]);
+}
+
+#[test]
+fn spawn_consuming() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let handle = rx.spawn_consuming(|it| it.collect::<Vec<usize>>());
+    sx.send(1).unwrap();
+    sx.send(2).unwrap();
+    sx.send(3).unwrap();
+    sx.close();
+    assert_eq!(handle.join().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn error_arc() {
+    #[derive(Debug)]
+    struct NotClone(usize);
+
+    let (sx, rx): (Sender<(), NotClone>, Receiver<(), NotClone>) = channel();
+    assert!(rx.error_arc().is_none());
+    sx.error(NotClone(4)).unwrap();
+    assert_eq!(rx.recv(), None);
+
+    let rx = Arc::new(rx);
+    let handles: Vec<_> = (0..2).map(|_| {
+        let rx = rx.clone();
+        ::std::thread::spawn(move || rx.error_arc().unwrap())
+    }).collect();
+    let errors: Vec<Arc<NotClone>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert!(Arc::ptr_eq(&errors[0], &errors[1]));
+    assert_eq!(errors[0].0, 4);
+    assert!(rx.take_error().is_none());
+    assert!(rx.has_error());
+    assert_eq!(rx.close_reason(), Some(CloseReason::Error));
+
+    assert!(rx.clear_error().is_none());
+    assert!(!rx.has_error());
+}
+
+#[test]
+fn drain_into() {
+    use std::collections::HashSet;
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.send(1).unwrap();
+    sx.send(2).unwrap();
+    let mut xs = vec![0];
+    rx.drain_into(&mut xs);
+    assert_eq!(xs, vec![0, 1, 2]);
+
+    sx.send(2).unwrap();
+    sx.send(3).unwrap();
+    sx.error(()).unwrap();
+    let mut set: HashSet<usize> = xs.into_iter().collect();
+    rx.drain_block_into(&mut set);
+    assert_eq!(set.len(), 4);
+    assert_eq!(rx.take_error(), Some(()));
+}
+
+#[test]
+fn arm_drop_error() {
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = channel();
+    let sx = sx.arm_drop_error("aborted");
+    sx.send(1).unwrap();
+    drop(sx);
+    assert_eq!(rx.recv(), Some(1));
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.take_error(), Some("aborted"));
+
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = channel();
+    sx.arm_drop_error("aborted").close();
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.take_error(), None);
+
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = channel();
+    sx.arm_drop_error("aborted").error("failed").unwrap();
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.take_errors(), vec!["failed"]);
+
+    // Passing the Sender on doesn't fire the error by itself.
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = channel();
+    let tx = sx.arm_drop_error("aborted").into_inner().unwrap();
+    tx.send(CommMsg::Message(1)).unwrap();
+    drop(tx);
+    assert_eq!(rx.recv_block(), Some(1));
+    assert_eq!(rx.recv_block(), None);
+    assert_eq!(rx.take_error(), None);
+
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = channel();
+    let (other, _other_rx) = channel();
+    let tee = sx.arm_drop_error("aborted").tee(other);
+    tee.send(1).unwrap();
+    tee.close();
+    assert_eq!(rx.drain(), vec![1]);
+    assert_eq!(rx.take_error(), None);
+
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = channel();
+    let mapped = sx.arm_drop_error("aborted").map(|x: usize| x * 2);
+    mapped.send(1).unwrap();
+    drop(mapped);
+    assert_eq!(rx.drain(), vec![2]);
+    assert_eq!(rx.take_error(), Some("aborted"));
+
+    // Dropping into a full bounded channel gives up on the error instead
+    // of blocking.
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = sync_channel(1);
+    let sx = sx.arm_drop_error("aborted");
+    sx.send(1).unwrap();
+    thread::spawn(move || drop(sx)).join().unwrap();
+    assert_eq!(rx.recv_block(), Some(1));
+    assert_eq!(rx.recv_block(), None);
+    assert_eq!(rx.take_error(), None);
+
+    let (sx, rx): (Sender<usize, &'static str>, Receiver<usize, &'static str>) = sync_channel(2);
+    let sx = sx.arm_drop_error("aborted");
+    sx.send(1).unwrap();
+    drop(sx);
+    assert_eq!(rx.recv_block(), Some(1));
+    assert_eq!(rx.recv_block(), None);
+    assert_eq!(rx.take_error(), Some("aborted"));
+}
+
+#[test]
+fn zip() {
+    let (sx1, rx1): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (sx2, rx2): (Sender<&'static str, String>, Receiver<&'static str, String>) = channel();
+    for &(i, s) in &[(1, "a"), (2, "b"), (3, "c")] {
+        sx1.send(i).unwrap();
+        sx2.send(s).unwrap();
+    }
+    sx1.send(4).unwrap();
+    drop(sx1);
+    let zipped = super::zip(rx1, rx2);
+    let xs: Vec<(usize, &'static str)> = zipped.blocking_iter().take(3).collect();
+    assert_eq!(xs, vec![(1, "a"), (2, "b"), (3, "c")]);
+    sx2.error("broken".to_string()).unwrap();
+    assert_eq!(zipped.recv_block(), None);
+    assert_eq!(zipped.take_error(), Some(Either::Right("broken".to_string())));
+
+    let (sx1, rx1): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (sx2, rx2): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx2.send(1).unwrap();
+    sx1.close();
+    let zipped = super::zip(rx1, rx2);
+    assert_eq!(zipped.recv_block(), None);
+    assert_eq!(zipped.take_error(), None);
+    drop(sx2);
+
+    // The second input closing is noticed while the first stays open and
+    // silent.
+    let (sx1, rx1): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (sx2, rx2): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let zipped = super::zip(rx1, rx2);
+    thread::sleep(Duration::from_millis(10));
+    sx2.close();
+    assert_eq!(zipped.recv_timeout(Duration::from_secs(5)), None);
+    assert!(zipped.is_closed());
+    drop(sx1);
+}
+
+#[test]
+fn polling_receives_only_stop_on_close() {
+    // Gaps longer than the 10ms poll interval of recv_block_cancellable.
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let producer = thread::spawn(move || {
+        for i in 0..4 {
+            thread::sleep(Duration::from_millis(25));
+            sx.send(i).unwrap();
+        }
+        thread::sleep(Duration::from_millis(25));
+    });
+
+    let token = CancelToken::new();
+    let mut xs = Vec::new();
+    while let Some(m) = rx.recv_block_cancellable(&token) {
+        xs.push(m);
+    }
+    assert_eq!(xs, vec![0, 1, 2, 3]);
+    assert!(rx.is_closed());
+    producer.join().unwrap();
+
+    // timeout_iter only gives up after a gap longer than its idle time.
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let producer = thread::spawn(move || {
+        for i in 0..4 {
+            thread::sleep(Duration::from_millis(25));
+            sx.send(i).unwrap();
+        }
+    });
+    let mut it = rx.timeout_iter(Duration::from_secs(5));
+    let xs: Vec<usize> = it.by_ref().collect();
+    assert_eq!(xs, vec![0, 1, 2, 3]);
+    assert_eq!(it.stopped_reason(), Some(StopReason::Closed));
+    producer.join().unwrap();
+}
+
+#[test]
+fn same_channel() {
+    let (sx, _rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let (other, _other_rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    assert!(sx.same_channel(&sx.clone()));
+    assert!(sx.same_channel(&sx.downgrade().upgrade().unwrap()));
+    assert!(!sx.same_channel(&other));
+}
+
+#[test]
+fn recv_guarded() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    assert!(rx.recv_guarded().is_none());
+    sx.send(1).unwrap();
+    sx.send(2).unwrap();
+
+    {
+        let guard = rx.recv_guarded().unwrap();
+        assert_eq!(*guard, 1);
+    }
+    let mut guard = rx.recv_guarded().unwrap();
+    assert_eq!(*guard, 1);
+    *guard += 10;
+    drop(guard);
+    assert_eq!(rx.recv_guarded().unwrap().ack(), 11);
+    assert_eq!(rx.recv(), Some(2));
+}
+
+#[test]
+fn iter_until_value() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    for &m in &[5, 7, 0, 9] {
+        sx.send(m).unwrap();
+    }
+    let xs: Vec<usize> = rx.iter_until_value(0).collect();
+    assert_eq!(xs, vec![5, 7]);
+    assert_eq!(rx.recv(), Some(9));
+}
+
+#[test]
+fn inject_error() {
+    let (sx, rx) = mpsc::channel();
+    let rx: Receiver<usize, &'static str> = Receiver::from_old(rx);
+    sx.send(CommMsg::Message(1)).unwrap();
+    sx.send(CommMsg::Message(2)).unwrap();
+    assert_eq!(rx.recv(), Some(1));
+
+    rx.inject_error("bad message");
+    assert!(rx.has_error());
+    assert!(rx.is_closed());
+    assert_eq!(rx.recv(), None);
+    assert_eq!(rx.take_error(), Some("bad message"));
+
+    rx.inject_error("retry");
+    assert_eq!(rx.clear_error(), Some("retry"));
+    assert_eq!(rx.recv(), Some(2));
+}
+
+#[test]
+fn reserve() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+    let permit = sx.reserve().unwrap();
+    assert_eq!(sx.send2(1), Err(TrySendError::Full(1)));
+    permit.send(2).unwrap();
+    assert_eq!(rx.recv(), Some(2));
+
+    drop(sx.reserve().unwrap());
+    assert_eq!(sx.send2(3), Ok(()));
+
+    // The channel is full, so reserving waits for the receiver.
+    let reserved = Arc::new(AtomicBool::new(false));
+    let flag = reserved.clone();
+    let producer = thread::spawn(move || {
+        let permit = sx.reserve().unwrap();
+        flag.store(true, Ordering::SeqCst);
+        permit.send(4).unwrap();
+        sx
+    });
+    thread::sleep(Duration::from_millis(50));
+    assert!(!reserved.load(Ordering::SeqCst));
+    assert_eq!(rx.recv_block(), Some(3));
+    let sx = producer.join().unwrap();
+    assert!(reserved.load(Ordering::SeqCst));
+    assert_eq!(rx.recv(), Some(4));
+
+    drop(rx);
+    assert_eq!(sx.reserve().err(), Some(ReserveError));
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.reserve().unwrap().send(1).unwrap();
+    assert_eq!(rx.recv(), Some(1));
+}
+
+#[test]
+fn into_vec_block() {
+    // close()
+    {
+        let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+        sx.send(5usize).unwrap();
+        sx.send(7usize).unwrap();
+        sx.send(9usize).unwrap();
+        sx.close();
+
+        assert_eq!(rx.into_vec_block(), Ok(vec![5,7,9]));
+    }
+    // error()
+    {
+        let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+        sx.send(5usize).unwrap();
+        sx.send(7usize).unwrap();
+        sx.send(9usize).unwrap();
+        sx.error(()).unwrap();
+
+        assert_eq!(rx.into_vec_block(), Err((vec![5,7,9], ())));
+    }
+}
+
+#[test]
+fn into_read() {
+    use std::io::{self, Read};
+
+    let (sx, rx): (Sender<Vec<u8>, io::Error>, Receiver<Vec<u8>, io::Error>) = channel();
+    sx.send(b"hello ".to_vec()).unwrap();
+    sx.send(Vec::new()).unwrap();
+    sx.send(b"world".to_vec()).unwrap();
+    sx.close();
+
+    let mut reader = rx.into_read();
+    let mut buf = [0u8; 4];
+    let mut out = Vec::new();
+    loop {
+        match reader.read(&mut buf).unwrap() {
+            0 => break,
+            n => out.extend_from_slice(&buf[..n])
+        }
+    }
+    assert_eq!(out, b"hello world".to_vec());
+
+    let (sx, rx): (Sender<Vec<u8>, io::Error>, Receiver<Vec<u8>, io::Error>) = channel();
+    sx.send(b"abc".to_vec()).unwrap();
+    sx.error(io::Error::new(io::ErrorKind::InvalidData, "bad chunk")).unwrap();
+    let mut reader = rx.into_read();
+    assert_eq!(reader.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"abc");
+    let err = reader.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn map_err() {
+    let (sx, rx): (Sender<usize, u32>, Receiver<usize, u32>) = channel();
+    let rx = rx.map_err(|e: u32| format!("error {}", e));
+
+    sx.send(5usize).unwrap();
+    sx.error(3).unwrap();
+
+    assert_eq!(rx.recv(), Some(5usize));
+    assert!(rx.recv_block().is_none());
+    assert!(rx.is_closed());
+    assert!(rx.has_error());
+    assert_eq!(rx.take_error(), Some("error 3".to_string()));
+    assert!(!rx.has_error());
+    assert!(rx.take_error().is_none());
+}
+
+#[test]
+fn comm_msg_eq() {
+    let m: CommMsg<usize, String> = CommMsg::Message(5);
+    let e: CommMsg<usize, String> = CommMsg::Error("hi".to_string());
+
+    assert_eq!(m.clone(), m);
+    assert_eq!(e.clone(), e);
+    assert!(m != e);
+    assert!(CommMsg::Message(5) != CommMsg::<usize, usize>::Error(5));
+}
+
+#[test]
+fn send_timeout() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = sync_channel(1);
+
+    sx.send_timeout(5usize, Duration::from_millis(10)).unwrap();
+    assert_eq!(sx.send_timeout(7usize, Duration::from_millis(10)),
+               Err(SendTimeoutError::Timeout(7usize)));
+    assert!(!sx.is_closed());
+    assert_eq!(rx.len(), 1);
+
+    assert_eq!(rx.recv(), Some(5usize));
+    sx.send_timeout(7usize, Duration::from_millis(10)).unwrap();
+    assert_eq!(rx.recv(), Some(7usize));
+
+    drop(rx);
+    assert_eq!(sx.send_timeout(9usize, Duration::from_millis(10)),
+               Err(SendTimeoutError::Disconnected(9usize)));
+    assert!(sx.is_closed());
+}
+
+#[test]
+fn sender_count() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    assert_eq!(rx.sender_count(), 1);
+
+    let sx2 = sx.clone();
+    let sx3 = sx2.clone();
+    assert_eq!(rx.sender_count(), 3);
+
+    sx.close();
+    sx2.error(()).unwrap();
+    assert_eq!(rx.sender_count(), 1);
+
+    drop(sx3);
+    assert_eq!(rx.sender_count(), 0);
+}
+
+#[test]
+fn try_iter() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+
+    sx.send(5usize).unwrap();
+    sx.send(7).unwrap();
+
+    let xs: Vec<usize> = rx.try_iter().collect();
+    assert_eq!(xs, vec![5,7]);
+    assert!(!rx.is_closed());
+}
+
+#[test]
+fn close_with_live_clone() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let sx2 = sx.clone();
+
+    let handle = thread::spawn(move || {
+        sx.send(5usize).unwrap();
+        sx.send(7usize).unwrap();
+        sx.close();
+    });
+
+    let xs: Vec<usize> = rx.blocking_iter().collect();
+    handle.join().unwrap();
+    assert_eq!(xs, vec![5,7]);
+    assert!(rx.is_closed());
+    assert!(!rx.has_error());
+
+    // sx2 is still alive, but anything it sends now is never received.
+    sx2.send(9usize).unwrap();
+    assert!(rx.recv().is_none());
+}
+
+#[test]
+fn receiver_map_filter() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    let rx = rx.filter(|x| *x != 7).map(|x| x.to_string());
+
+    sx.send(5usize).unwrap();
+    sx.send(7usize).unwrap();
+    sx.send(9usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    let xs: Vec<String> = rx.blocking_iter().collect();
+    assert_eq!(xs, vec!["5".to_string(), "9".to_string()]);
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    let rx = rx.map(|x| x * 2);
+    let _sx2 = sx.clone();
+    sx.send(5usize).unwrap();
+    sx.close();
+    assert_eq!(rx.blocking_iter().collect::<Vec<usize>>(), vec![10]);
+}
+
+#[test]
+fn close_reason() {
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    assert_eq!(rx.close_reason(), None);
+    sx.send(5usize).unwrap();
+    sx.close();
+    assert_eq!(rx.recv(), Some(5usize));
+    assert_eq!(rx.close_reason(), None);
+    assert!(rx.recv().is_none());
+    assert_eq!(rx.close_reason(), Some(CloseReason::Clean));
+
+    let (sx, rx): (Sender<usize, ()>, Receiver<usize, ()>) = channel();
+    sx.error(()).unwrap();
+    assert!(rx.recv().is_none());
+    assert_eq!(rx.close_reason(), Some(CloseReason::Error));
+    rx.take_error();
+    assert_eq!(rx.close_reason(), Some(CloseReason::Error));
+}
+
+#[test]
+fn iter_into_error() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    sx.send(5usize).unwrap();
+    sx.error("hi".to_string()).unwrap();
+
+    let mut iter = rx.into_blocking_iter();
+    let xs: Vec<usize> = iter.by_ref().collect();
+    assert_eq!(xs, vec![5]);
+    assert_eq!(iter.into_error(), Some("hi".to_string()));
+
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+    sx.close();
+    let mut iter = rx.into_iter();
+    assert!(iter.next().is_none());
+    assert!(iter.into_error().is_none());
+}
+
+#[test]
+fn send_result() {
+    let (sx, rx): (Sender<usize, String>, Receiver<usize, String>) = channel();
+
+    sx.send_result(Ok(5usize)).unwrap();
+    sx.send_result(Err("hi".to_string())).unwrap();
+
+    assert_eq!(rx.recv(), Some(5usize));
+    assert!(rx.recv().is_none());
+    assert_eq!(rx.take_error(), Some("hi".to_string()));
+
+    drop(rx);
+    assert_eq!(sx.send_result(Ok(7usize)), Err(SendResultError::Message(7usize)));
+    assert_eq!(sx.send_result(Err("x".to_string())), Err(SendResultError::Error("x".to_string())));
 }